@@ -0,0 +1,167 @@
+use super::gif;
+use super::json::Json;
+use super::octo;
+
+// Sizes of memory Octo's platforms allow programs to fill, used to tell which
+// platform a cartridge was made for.
+const CHIP8_MAX_SIZE: f64 = 3216.0;
+const SCHIP_MAX_SIZE: f64 = 3583.0;
+
+/// A program exported from Octo as a "cartridge" GIF. The GIF shows a label,
+/// and the program travels hidden in the low four bits of each pixel's colour
+/// index, two pixels to a byte with the high nybble first, running through
+/// every frame. The bytes start with the length of the payload as a 32-bit
+/// big endian number, followed by the payload: a JSON object holding the
+/// program and the options it was saved with.
+pub struct Cartridge {
+    pub program: Vec<u8>,
+
+    // Settings from the cartridge's options as keys of the config file, such
+    // as ("palette", "#000000,#FFCC00").
+    pub options: Vec<(String, String)>,
+}
+
+impl Cartridge {
+    pub fn decode(data: &[u8]) -> Result<Cartridge, String> {
+        let frames = try!(gif::decode_frames(data));
+
+        let mut bytes = Vec::new();
+        for frame in frames.iter() {
+            for pair in frame.chunks(2) {
+                if pair.len() == 2 {
+                    bytes.push((pair[0] & 0x0F) << 4 | (pair[1] & 0x0F));
+                }
+            }
+        }
+
+        if bytes.len() < 4 {
+            return Err("cartridge has no payload".to_string());
+        }
+        let size = (bytes[0] as usize) << 24 | (bytes[1] as usize) << 16 |
+                   (bytes[2] as usize) << 8 | bytes[3] as usize;
+        if size == 0 || bytes.len() < 4 + size {
+            return Err("cartridge payload is truncated".to_string());
+        }
+
+        // Octo writes each character of the JSON as a single byte.
+        let json: String = bytes[4..4 + size].iter().map(|&byte| byte as char).collect();
+        let payload = try!(Json::parse(&json).map_err(|why| format!("cartridge payload: {}", why)));
+
+        let source = match payload.get("program") {
+            Some(&Json::String(ref source)) => source,
+            _ => return Err("cartridge payload has no program".to_string()),
+        };
+
+        let program = try!(octo::assemble(source).map_err(|why| format!("cartridge program: {}", why)));
+        if program.is_empty() {
+            return Err("cartridge program is empty".to_string());
+        }
+
+        Ok(Cartridge {
+            program: program,
+            options: payload.get("options").map_or(Vec::new(), convert_options),
+        })
+    }
+}
+
+/// Translates Octo's options into settings.
+fn convert_options(options: &Json) -> Vec<(String, String)> {
+    let mut settings = Vec::new();
+
+    if let Some(&Json::Number(tickrate)) = options.get("tickrate") {
+        if tickrate >= 1.0 {
+            settings.push(("instructions-per-frame".to_string(), format!("{}", tickrate as u64)));
+        }
+    }
+
+    if let Some(&Json::Number(max_size)) = options.get("maxSize") {
+        let platform = if max_size <= CHIP8_MAX_SIZE {
+            "chip8"
+        } else if max_size <= SCHIP_MAX_SIZE {
+            "schip"
+        } else {
+            "xochip"
+        };
+        settings.push(("platform".to_string(), platform.to_string()));
+    }
+
+    // Octo's load/store quirk leaves I alone, the opposite of notch's.
+    // Cartridges saved without quirks keep notch's own.
+    let quirk_names = ["shiftQuirks", "loadStoreQuirks", "jumpQuirks", "logicQuirks", "clipQuirks"];
+    let quirk = |name: &str| options.get(name).map_or(false, |value| *value == Json::Bool(true));
+    let mut quirks = Vec::new();
+    if quirk("shiftQuirks") {
+        quirks.push("shift");
+    }
+    if !quirk("loadStoreQuirks") {
+        quirks.push("load-store");
+    }
+    if quirk("jumpQuirks") {
+        quirks.push("jump");
+    }
+    if quirk("logicQuirks") {
+        quirks.push("vf-reset");
+    }
+    if quirk("clipQuirks") {
+        quirks.push("clip");
+    }
+    if quirk_names.iter().any(|name| options.get(name).is_some()) {
+        let list = if quirks.is_empty() { "none".to_string() } else { quirks.join(", ") };
+        settings.push(("quirks".to_string(), list));
+    }
+
+    // Colours in the order of notch's palette.
+    let colors: Vec<&str> = ["backgroundColor", "fillColor", "fillColor2", "blendColor"].iter()
+        .filter_map(|&name| match options.get(name) {
+            Some(&Json::String(ref color)) => Some(color.as_str()),
+            _ => None,
+        })
+        .collect();
+    if colors.len() == 4 {
+        settings.push(("palette".to_string(), colors.join(",")));
+    }
+
+    settings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Cartridge;
+
+    // A cartridge holding the Octo source of a bouncing ball, saved with
+    // CHIP-8 settings and Octo's amber colours.
+    const BOUNCE: &'static [u8] = include_bytes!("../tests/fixtures/bounce.gif");
+
+    #[test]
+    fn assembles_the_program_of_a_cartridge() {
+        let cartridge = Cartridge::decode(BOUNCE).unwrap();
+        assert_eq!(cartridge.program, vec![
+            0x12, 0x06, 0x60, 0xF0, 0xF0, 0x60, 0x60, 0x0A, 0x61, 0x05, 0x62, 0x01,
+            0x63, 0x01, 0xA2, 0x02, 0xD0, 0x14, 0x22, 0x34, 0xD0, 0x14, 0x80, 0x24,
+            0x81, 0x34, 0x40, 0x00, 0x62, 0x01, 0x6F, 0x3C, 0x8F, 0x07, 0x3F, 0x00,
+            0x62, 0xFF, 0x41, 0x00, 0x63, 0x01, 0x6F, 0x1C, 0x8F, 0x17, 0x3F, 0x00,
+            0x63, 0xFF, 0x12, 0x10, 0x64, 0x02, 0xF4, 0x15, 0xF4, 0x07, 0x44, 0x00,
+            0x12, 0x40, 0x12, 0x38, 0x00, 0xEE,
+        ]);
+    }
+
+    #[test]
+    fn converts_the_options_of_a_cartridge() {
+        let cartridge = Cartridge::decode(BOUNCE).unwrap();
+        let options: Vec<(&str, &str)> = cartridge.options.iter()
+            .map(|&(ref key, ref value)| (key.as_str(), value.as_str()))
+            .collect();
+        assert_eq!(options, vec![
+            ("instructions-per-frame", "20"),
+            ("platform", "chip8"),
+            ("quirks", "load-store, vf-reset, clip"),
+            ("palette", "#996600,#FFCC00,#FF6600,#662200"),
+        ]);
+    }
+
+    #[test]
+    fn refuses_images_without_a_payload() {
+        assert!(Cartridge::decode(b"GIF89a").is_err());
+        assert!(Cartridge::decode(&BOUNCE[..BOUNCE.len() / 2]).is_err());
+    }
+}
@@ -0,0 +1,127 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// Settings loaded from a configuration file. The file is made up of
+/// `key = value` pairs grouped by `[section]` headers. Lines starting with `#`
+/// or `;` are comments; inline comments are not supported since `#` is used in
+/// colour values. Keys that appear before any section header belong to the ""
+/// section.
+///
+///     [display]
+///     palette = amber
+#[derive(Debug, Default)]
+pub struct Config {
+    sections: HashMap<String, HashMap<String, String>>,
+}
+
+impl Config {
+    /// Reads and parses a configuration file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Config, String> {
+        let filename = format!("{}", path.as_ref().display());
+        let mut contents = String::new();
+
+        match File::open(path) {
+            Ok(ref mut file) => {
+                if let Err(why) = file.read_to_string(&mut contents) {
+                    return Err(format!("cannot read '{}': {}", filename, why));
+                }
+            },
+            Err(why) => {
+                return Err(format!("cannot open '{}': {}", filename, why));
+            },
+        };
+
+        Config::parse(&contents).map_err(|why| format!("{}: {}", filename, why))
+    }
+
+    /// Parses the contents of a configuration file.
+    pub fn parse(contents: &str) -> Result<Config, String> {
+        let mut config = Config::default();
+        let mut section = String::new();
+
+        for (number, line) in contents.lines().enumerate() {
+            // Skip blank lines and comments.
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+
+            if line.starts_with('[') && line.ends_with(']') {
+                section = line[1..line.len() - 1].trim().to_string();
+                continue;
+            }
+
+            match line.find('=') {
+                Some(pos) => {
+                    let key = line[..pos].trim().to_string();
+                    let value = line[pos + 1..].trim().to_string();
+                    config.set(&section, &key, &value);
+                },
+                None => {
+                    return Err(format!("line {}: expected 'key = value'", number + 1));
+                },
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// Returns the default location of the configuration file which lives in
+    /// the user's configuration directory.
+    pub fn default_path() -> Option<PathBuf> {
+        config_dir().map(|dir| dir.join("notch.conf"))
+    }
+
+    /// Looks up the value of a key in a section.
+    pub fn get(&self, section: &str, key: &str) -> Option<&str> {
+        self.sections.get(section)
+            .and_then(|keys| keys.get(key))
+            .map(|value| value.as_str())
+    }
+
+    /// Returns every key and value in a section, sorted by key.
+    pub fn entries(&self, section: &str) -> Vec<(&str, &str)> {
+        let mut entries: Vec<(&str, &str)> = match self.sections.get(section) {
+            Some(keys) => keys.iter().map(|(key, value)| (key.as_str(), value.as_str())).collect(),
+            None => Vec::new(),
+        };
+        entries.sort();
+        entries
+    }
+
+    /// Sets the value of a key in a section, replacing any previous value.
+    pub fn set(&mut self, section: &str, key: &str, value: &str) {
+        self.sections.entry(section.to_string())
+            .or_insert_with(HashMap::new)
+            .insert(key.to_string(), value.to_string());
+    }
+}
+
+/// Finds the directory notch stores its configuration in, following the XDG
+/// base directory specification on unix-like systems.
+pub fn config_dir() -> Option<PathBuf> {
+    if let Some(dir) = env::var_os("XDG_CONFIG_HOME") {
+        return Some(PathBuf::from(dir).join("notch"));
+    }
+    if let Some(dir) = env::var_os("APPDATA") {
+        return Some(PathBuf::from(dir).join("notch"));
+    }
+
+    env::home_dir().map(|home| home.join(".config").join("notch"))
+}
+
+/// Finds the directory notch keeps data such as saved flags in, following
+/// the XDG base directory specification on unix-like systems.
+pub fn data_dir() -> Option<PathBuf> {
+    if let Some(dir) = env::var_os("XDG_DATA_HOME") {
+        return Some(PathBuf::from(dir).join("notch"));
+    }
+    if let Some(dir) = env::var_os("APPDATA") {
+        return Some(PathBuf::from(dir).join("notch"));
+    }
+
+    env::home_dir().map(|home| home.join(".local").join("share").join("notch"))
+}
@@ -1,21 +1,16 @@
-use std::thread::sleep;
-use std::time::Duration;
-
 use super::rand::random;
-use super::time;
 
-use super::memory::END_RESERVED;
+use super::flags::FLAG_COUNT;
+use super::display::{DISPLAY_HEIGHT, DISPLAY_WIDTH, HIRES_HEIGHT, HIRES_WIDTH};
+use super::frontend::{CpuState, HostEvent};
 use super::interconnect::Interconnect;
+use super::memory::Protection;
+use super::quirks::Quirks;
+use super::settings::Settings;
 
 // Instructions are 2 bytes long and stored as BigEndian.
 const INSTRUCTION_SIZE: u16 = 2;
 
-// Round about 60Hz delay for timers.
-const TIMER_DELAY: u64 = 16;
-
-// Around 500Hz clock speed.
-const EXECUTION_DELAY: u64 = 2;
-
 #[derive(Debug)]
 pub struct Cpu {
     // Interconnect has access to the memory and other external resources.
@@ -55,18 +50,28 @@ pub struct Cpu {
     dt: u8,
     st: u8,
 
-    dt_end: u64,
-    st_end: u64,
+    // Instructions executed since the start of the current frame.
+    cycles: u32,
+
+    // Instructions executed every 60Hz frame. The default of 8 gives a clock
+    // speed of around 500Hz.
+    cycles_per_frame: u32,
+
+    // Behaviours of the interpreter the rom was written for.
+    quirks: Quirks,
+
+    // Address the rom is loaded at, where execution starts.
+    load_address: u16,
 }
 
 impl Cpu {
-    pub fn new(interconnect: Interconnect) -> Cpu {
+    pub fn new(interconnect: Interconnect, settings: &Settings) -> Cpu {
         Cpu {
             // Interconnect is used to control system resources like rom and memory.
             interconnect: interconnect,
 
-            // Program counter.
-            pc: END_RESERVED as u16,
+            // Program counter, starting at the beginning of the rom.
+            pc: settings.load_address as u16,
 
             // The function call stack.
             stack: [0; 16],
@@ -99,44 +104,93 @@ impl Cpu {
             dt: 0,
             st: 0,
 
-            dt_end: 0,
-            st_end: 0,
+            cycles: 0,
+            cycles_per_frame: settings.instructions_per_frame,
+            quirks: settings.quirks,
+            load_address: settings.load_address as u16,
         }
     }
 
-    /// Execute instructions from ram.
-    pub fn run(&mut self) {
-        loop {
-            // Interconnect can signal the emulator to halt.
-            // This is because interconnect works with the native window system
-            // and handles close events.
-            if self.interconnect.input.close_requested {
-                break
-            }
+    /// Resets the cpu to its power on state and loads a new rom. The
+    /// interconnect keeps its window and input state so a reload is seamless.
+    /// Nothing changes if the rom cannot be loaded.
+    pub fn reset(&mut self, rom: Vec<u8>) -> Result<(), String> {
+        try!(self.interconnect.reset(rom));
 
-            // Read a word from ram where the program counter currently points
-            // to execute.
-            let word = self.interconnect.memory.read_word(self.pc);
+        self.pc = self.load_address;
+        self.stack = [0; 16];
+        self.sp = 0;
 
-            // Execute until the subroutine ends if we are in one.
-            if self.execute_instruction(word) {
-                break
-            }
+        for reg in 0x0..0x10 {
+            self.set_reg(reg, 0);
+        }
 
-            // Poll for input and set the input state.
-            self.interconnect.input.handle_input();
+        self.i = 0;
+        self.dt = 0;
+        self.st = 0;
+        self.cycles = 0;
+        Ok(())
+    }
 
-            // Monitor the beeping state.
-            self.interconnect.sound.handle_sound();
-        }
+    /// Returns true when the interconnect signals the emulator to halt. This
+    /// is because interconnect works with the native window system and handles
+    /// close events.
+    pub fn halted(&self) -> bool {
+        self.interconnect.input.close_requested
     }
 
-    #[inline(always)]
-    fn execute_instruction(&mut self, instr: u16) -> bool {
+    /// Returns true while the user has paused the virtual machine.
+    pub fn paused(&self) -> bool {
+        self.interconnect.input.paused
+    }
+
+    /// Keeps the frontend responsive while paused without running any
+    /// instructions.
+    pub fn idle_frame(&mut self) {
+        let state = self.state();
+        self.interconnect.idle_frame(&state);
+    }
+
+    /// Returns the address of the next instruction to execute.
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    /// Returns the number of frames run since the rom was loaded.
+    pub fn frame(&self) -> u64 {
+        self.interconnect.frame
+    }
+
+    /// Passes input from a source other than the frontend to the
+    /// interconnect.
+    pub fn handle_event(&mut self, event: HostEvent) {
+        self.interconnect.handle_event(event);
+    }
+
+    /// Execute a single instruction from ram. Frames are counted in executed
+    /// instructions rather than wall clock time so emulation is deterministic.
+    /// Returns true when the instruction was the last one of a frame.
+    pub fn step(&mut self) -> bool {
+        // Read a word from ram where the program counter currently points
+        // to execute.
+        let pc = self.pc;
+        let word = self.interconnect.memory.fetch(pc);
+        self.execute_instruction(word);
+        self.check_violations(pc, word);
+
+        self.cycles += 1;
+        if self.cycles < self.cycles_per_frame {
+            return false;
+        }
+        self.cycles = 0;
+
         self.handle_timers();
+        true
+    }
 
+    #[inline(always)]
+    fn execute_instruction(&mut self, instr: u16) {
         let opcode = (instr >> 12) as u8;
-        let mut ret: bool = false;
         let mut skip: bool = false;
 
         match opcode {
@@ -148,19 +202,61 @@ impl Cpu {
                         // 00E0 - CLS
                         // Clears the screen.
 
-                        self.interconnect.graphics.clear_display();
+                        self.interconnect.display.clear();
                     },
                     0xEE => {
                         // 00EE - RET
                         // Returns from a subroutine.
 
+                        // The stack holds the address of the call, so the
+                        // program counter is incremented past it as usual.
                         self.sp -= 1;
                         self.pc = self.stack[self.sp as usize];
                         self.stack[self.sp as usize] = 0;
+                    },
+                    0xC0...0xCF => {
+                        // 00CN - SCD N
+                        // Scrolls the display down N lines. SCHIP only.
+
+                        let lines = (identifier & 0x0F) as usize;
+                        self.interconnect.display.scroll_down(lines);
+                    },
+                    0xD0...0xDF => {
+                        // 00DN - SCU N
+                        // Scrolls the display up N lines. XO-CHIP only.
+
+                        let lines = (identifier & 0x0F) as usize;
+                        self.interconnect.display.scroll_up(lines);
+                    },
+                    0xFB => {
+                        // 00FB - SCR
+                        // Scrolls the display right 4 pixels. SCHIP only.
+
+                        self.interconnect.display.scroll_right(4);
+                    },
+                    0xFC => {
+                        // 00FC - SCL
+                        // Scrolls the display left 4 pixels. SCHIP only.
 
-                        // Break out of the execution loop for the current
-                        // subroutine.
-                        ret = true;
+                        self.interconnect.display.scroll_left(4);
+                    },
+                    0xFD => {
+                        // 00FD - EXIT
+                        // Stops the interpreter. SCHIP only.
+
+                        self.interconnect.input.close_requested = true;
+                    },
+                    0xFE => {
+                        // 00FE - LOW
+                        // Switches to the 64x32 display. SCHIP only.
+
+                        self.interconnect.display.set_resolution(DISPLAY_WIDTH, DISPLAY_HEIGHT);
+                    },
+                    0xFF => {
+                        // 00FF - HIGH
+                        // Switches to the 128x64 display. SCHIP only.
+
+                        self.interconnect.display.set_resolution(HIRES_WIDTH, HIRES_HEIGHT);
                     },
                     _ => {
                         // 0NNN - SYS NNN
@@ -197,7 +293,7 @@ impl Cpu {
                 // Set the program counter to the call address begin executing
                 // the subroutine.
                 self.pc = addr;
-                self.run();
+                skip = true;
             },
             0x3 => {
                 // 3XNN - SE VX, NN
@@ -209,7 +305,7 @@ impl Cpu {
                 let x = self.get_reg(regx);
 
                 if x == byte {
-                    self.pc += INSTRUCTION_SIZE;
+                    self.skip_next_instruction();
                 }
             },
             0x4 => {
@@ -223,21 +319,59 @@ impl Cpu {
                 let x = self.get_reg(regx);
 
                 if x != byte {
-                    self.pc += INSTRUCTION_SIZE;
+                    self.skip_next_instruction();
                 }
             },
             0x5 => {
-                // 5XY0 - SE VX, VY
-                //
-                // Skip the next instruction if VX == VY.
-
                 let regx = ((instr << 4) >> 12) as u8;
                 let regy = ((instr << 8) >> 12) as u8;
-                let x = self.get_reg(regx);
-                let y = self.get_reg(regy);
+                let identifier = (instr & 0xF) as u8;
+
+                // Registers from VX to VY, counting down if Y is below X.
+                let count = (regx as i8 - regy as i8).abs() as usize + 1;
+                let register = |n: usize| if regx <= regy { regx + n as u8 } else { regx - n as u8 };
+
+                match identifier {
+                    0x0 => {
+                        // 5XY0 - SE VX, VY
+                        //
+                        // Skip the next instruction if VX == VY.
 
-                if x == y {
-                    self.pc += INSTRUCTION_SIZE;
+                        let x = self.get_reg(regx);
+                        let y = self.get_reg(regy);
+
+                        if x == y {
+                            self.skip_next_instruction();
+                        }
+                    },
+                    0x2 => {
+                        // 5XY2 - LD [I], VX - VY
+                        //
+                        // Copies VX through VY into memory starting at I,
+                        // leaving I as it is. XO-CHIP only.
+
+                        let i = self.i as usize;
+                        for n in 0..count {
+                            let val = self.get_reg(register(n));
+                            self.interconnect.memory.write(i + n, val);
+                        }
+                    },
+                    0x3 => {
+                        // 5XY3 - LD VX - VY, [I]
+                        //
+                        // Fills VX through VY from memory starting at I,
+                        // leaving I as it is. XO-CHIP only.
+
+                        let i = self.i as usize;
+                        for n in 0..count {
+                            let mem = self.interconnect.memory.read(i + n);
+                            self.set_reg(register(n), mem);
+                        }
+                    },
+                    _ => {
+                        println!("cpu: {:#?}", self);
+                        panic!("Found unknown identifier at instruction: {:#x}, addr: {:#x}", instr, self.pc);
+                    },
                 }
             },
             0x6 => {
@@ -281,6 +415,9 @@ impl Cpu {
                         let x = self.get_reg(regx);
                         let y = self.get_reg(regy);
                         self.set_reg(regx, x | y);
+                        if self.quirks.vf_reset {
+                            self.vf = 0;
+                        }
                     },
                     0x2 => {
                         // 8XY2 - AND VX, VY
@@ -290,6 +427,9 @@ impl Cpu {
                         let x = self.get_reg(regx);
                         let y = self.get_reg(regy);
                         self.set_reg(regx, x & y);
+                        if self.quirks.vf_reset {
+                            self.vf = 0;
+                        }
                     },
                     0x3 => {
                         // 8XY3 - XOR VX, VY
@@ -299,6 +439,9 @@ impl Cpu {
                         let x = self.get_reg(regx);
                         let y = self.get_reg(regy);
                         self.set_reg(regx, x ^ y);
+                        if self.quirks.vf_reset {
+                            self.vf = 0;
+                        }
                     },
                     0x4 => {
                         // 8XY4 - ADD VX, VY
@@ -341,9 +484,10 @@ impl Cpu {
                         // 8XY6 - SHR VX {, VY}
                         //
                         // If the least-significant bit of VX is 1, then VF is
-                        // set to 1, otherwise 0. Then VX is divided by 2.
+                        // set to 1, otherwise 0. Then VX is divided by 2. The
+                        // original interpreter shifts VY into VX instead.
 
-                        let x = self.get_reg(regx);
+                        let x = self.shift_source(regx, regy);
                         let lsb = x & 0x1;
 
                         self.vf = lsb;
@@ -372,8 +516,9 @@ impl Cpu {
                         //
                         // If the most-significant bit of VX is 1, then VF is
                         // set to 1, otherwise to 0. Then VX is multiplied by 2.
+                        // The original interpreter shifts VY into VX instead.
 
-                        let x = self.get_reg(regx);
+                        let x = self.shift_source(regx, regy);
                         let msb = (x & 0x80) >> 7;
 
                         self.vf = msb;
@@ -396,7 +541,7 @@ impl Cpu {
                 let y = self.get_reg(regy);
 
                 if x != y {
-                    self.pc += INSTRUCTION_SIZE;
+                    self.skip_next_instruction();
                 }
             },
             0xa => {
@@ -411,9 +556,16 @@ impl Cpu {
                 // BNNN - JP V0, NNN
                 //
                 // The program counter is set to NNN plus the value of V0.
+                // SUPER-CHIP adds VX instead, where X is the highest nibble
+                // of NNN.
 
                 let addr = ((instr << 4) >> 4) as u16;
-                self.pc = addr.wrapping_add(self.v0 as u16);
+                let offset = if self.quirks.jump {
+                    self.get_reg((addr >> 8) as u8)
+                } else {
+                    self.v0
+                };
+                self.pc = addr.wrapping_add(offset as u16);
                 skip = true;
             },
             0xc => {
@@ -439,15 +591,19 @@ impl Cpu {
                 // the number of 8bit rows that need to be drawn. If N is
                 // greater than 1, second line continues at position VX, VY+1,
                 // and so on.
+                //
+                // DXY0 draws a 16x16 sprite of 32 bytes instead, two bytes a
+                // row. SCHIP only.
 
                 let regx = ((instr << 4) >> 12) as u8;
                 let regy = ((instr << 8) >> 12) as u8;
                 let nibble = ((instr << 12) >> 12) as usize;
+                let (sprite_width, len) = if nibble == 0 { (16, 32) } else { (8, nibble) };
 
-                // Read N (nibble) bytes out out of memory starting at address
-                // register I into our sprite.
-                let mut sprite = vec![0 as u8; nibble];
-                for i in 0..nibble {
+                // Read the rows out of memory starting at address register I
+                // into our sprite.
+                let mut sprite = vec![0 as u8; len];
+                for i in 0..len {
                     sprite[i] = self.interconnect.memory.read(self.i as usize + i);
                 }
 
@@ -456,7 +612,8 @@ impl Cpu {
                 let y = self.get_reg(regy);
 
                 // Draw the sprite and store collision detection results in vf.
-                self.vf = self.interconnect.graphics.draw(x as usize, y as usize, sprite);
+                self.vf = self.interconnect.display.draw(x as usize, y as usize, sprite,
+                                                        sprite_width, self.quirks.clip);
             },
             0xe => {
                 let regx = ((instr << 4) >> 12) as u8;
@@ -469,9 +626,10 @@ impl Cpu {
                         // Skips the next instruction if the key stored in VX
                         // is pressed.
 
-                        let x = self.get_reg(regx);
+                        // Only the low nibble names a key.
+                        let x = self.get_reg(regx) & 0xF;
                         if self.interconnect.input.input_state[x as usize] {
-                            self.pc += INSTRUCTION_SIZE;
+                            self.skip_next_instruction();
                         }
                     },
                     0xa1 => {
@@ -480,9 +638,10 @@ impl Cpu {
                         // Skips the next instruction if the key stored in VX
                         // isn't pressed.
 
-                        let x = self.get_reg(regx);
+                        // Only the low nibble names a key.
+                        let x = self.get_reg(regx) & 0xF;
                         if !self.interconnect.input.input_state[x as usize] {
-                            self.pc += INSTRUCTION_SIZE;
+                            self.skip_next_instruction();
                         }
                     },
                     _ => {
@@ -496,6 +655,17 @@ impl Cpu {
                 let identifier = ((instr << 8) >> 8) as u8;
 
                 match identifier {
+                    0x00 if regx == 0 => {
+                        // F000 NNNN - LD I, NNNN
+                        //
+                        // Sets I to the 16-bit address stored in the two bytes
+                        // after the instruction, which is skipped over with
+                        // them. XO-CHIP only.
+
+                        let operand = self.pc.wrapping_add(INSTRUCTION_SIZE);
+                        self.i = self.interconnect.memory.fetch(operand);
+                        self.pc = operand;
+                    },
                     0x07 => {
                         // FX07 - LD VX, DT
                         //
@@ -507,11 +677,15 @@ impl Cpu {
                     0x0a => {
                         // FX0A - LD VX, N
                         //
-                        // All execution stops until a key is pressed, then the
-                        // value of that key is stored in VX.
-
-                        let key = self.interconnect.input.wait_input();
-                        self.set_reg(regx, key);
+                        // All execution stops until a key is pressed and
+                        // released, then the value of that key is stored in
+                        // VX. The instruction runs again every cycle until
+                        // then, so timers and the display keep going.
+
+                        match self.interconnect.input.wait_key_release() {
+                            Some(key) => self.set_reg(regx, key),
+                            None => skip = true,
+                        }
                     },
                     0x15 => {
                         // FX15 - LD DT, VX
@@ -592,6 +766,9 @@ impl Cpu {
                             let val = self.get_reg(register as u8);
                             self.interconnect.memory.write(i + register, val);
                         }
+                        if self.quirks.load_store {
+                            self.i = self.i.wrapping_add(end_reg as u16);
+                        }
                     },
                     0x65 => {
                         // FX65 - LD VX, [I]
@@ -606,6 +783,34 @@ impl Cpu {
                             let mem = self.interconnect.memory.read(i + register);
                             self.set_reg(register as u8, mem);
                         }
+                        if self.quirks.load_store {
+                            self.i = self.i.wrapping_add(end_reg as u16);
+                        }
+                    },
+                    0x75 => {
+                        // FX75 - LD R, VX
+                        //
+                        // SCHIP: Stores V0 through VX in the RPL user flags.
+                        // The flags are saved to disk so they outlast the
+                        // session.
+
+                        let end_reg = (regx as usize + 1).min(FLAG_COUNT);
+                        let values: Vec<u8> = (0..end_reg)
+                            .map(|register| self.get_reg(register as u8))
+                            .collect();
+                        self.interconnect.flags.save(&values);
+                    },
+                    0x85 => {
+                        // FX85 - LD VX, R
+                        //
+                        // SCHIP: Restores V0 through VX from the RPL user
+                        // flags.
+
+                        let end_reg = (regx as usize + 1).min(FLAG_COUNT);
+                        for register in 0..end_reg {
+                            let value = self.interconnect.flags.get(register);
+                            self.set_reg(register as u8, value);
+                        }
                     },
                     _ => {
                         println!("cpu: {:#?}", self);
@@ -620,44 +825,69 @@ impl Cpu {
         }
 
         // Increment the program counter to the next instruction.
-        if !ret && !skip {
-            self.pc += INSTRUCTION_SIZE;
+        if !skip {
+            self.pc = self.pc.wrapping_add(INSTRUCTION_SIZE);
         }
+    }
 
-        // By default the execution loop in not broken. True will be returned
-        // only by a successful RET instruction is executed.
-        ret
+    /// Skips the instruction after the current one. XO-CHIP's F000 NNNN is
+    /// twice as long as other instructions, so it is skipped whole.
+    fn skip_next_instruction(&mut self) {
+        let next = self.pc.wrapping_add(INSTRUCTION_SIZE);
+        let size = if self.interconnect.memory.read_word(next) == 0xF000 {
+            2 * INSTRUCTION_SIZE
+        } else {
+            INSTRUCTION_SIZE
+        };
+        self.pc = self.pc.wrapping_add(size);
     }
 
-    /// Handle the delay timer and play sounds.
+    /// Count down the timers at the end of every frame and play sounds. The
+    /// interconnect then presents the display and polls for input.
     fn handle_timers(&mut self) {
-        let current_time = time::precise_time_ns() / 1000000;
+        if self.dt > 0 {
+            self.dt -= 1;
+        }
 
-        let dt_enabled = self.dt > 0;
-        let st_enabled = self.st > 0;
+        // The beep plays for as long as the sound timer is active.
+        let beeping = self.st > 0;
+        if beeping {
+            self.st -= 1;
+        }
 
-        if st_enabled {
-            self.interconnect.sound.beeping = true;
+        let state = self.state();
+        self.interconnect.end_frame(beeping, &state);
+    }
 
-            if current_time >= self.st_end {
-                self.st -= 1;
-                self.st_end = current_time + TIMER_DELAY;
-            }
-        } else {
-            self.interconnect.sound.beeping = false;
+    /// Collects the registers that point into memory for the frontend.
+    fn state(&self) -> CpuState {
+        CpuState {
+            pc: self.pc,
+            i: self.i,
+            stack: self.stack[..self.sp as usize].to_vec(),
         }
+    }
 
-        if dt_enabled {
-            // TODO: Replace with time check.
-            //sleep(Duration::from_millis(TIMER_DELAY));
+    /// Reports writes to protected memory made by the instruction just
+    /// executed, pausing the program if protection is set to break.
+    fn check_violations(&mut self, pc: u16, instr: u16) {
+        let violations = self.interconnect.memory.take_violations();
+        if violations.is_empty() {
+            return;
+        }
 
-            if current_time >= self.dt_end {
-                self.dt -= 1;
-                self.dt_end = current_time + TIMER_DELAY;
-            }
+        for violation in violations.iter() {
+            let region = if violation.executed { "executed code" } else { "reserved memory" };
+            println!("notch: instruction {:04x} at {:#05x} wrote to {} at {:#05x}",
+                     instr, pc, region, violation.addr);
         }
 
-        sleep(Duration::from_millis(EXECUTION_DELAY));
+        if self.interconnect.memory.protection() == Protection::Break {
+            println!("cpu: {:#?}", self);
+            if self.interconnect.break_execution() {
+                println!("notch: paused, press F9 to resume");
+            }
+        }
     }
 
     /// Gets the value at a specified register.
@@ -685,6 +915,16 @@ impl Cpu {
         }
     }
 
+    /// Gets the register shifted by 8XY6 and 8XYE, which is VX with the shift
+    /// quirk and VY on the original interpreter.
+    fn shift_source(&mut self, regx: u8, regy: u8) -> u8 {
+        if self.quirks.shift {
+            self.get_reg(regx)
+        } else {
+            self.get_reg(regy)
+        }
+    }
+
     /// Sets the value of a general purpose register.
     fn set_reg(&mut self, reg: u8, byte: u8) {
         match reg {
@@ -710,3 +950,106 @@ impl Cpu {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Cpu;
+    use super::super::frontend::{FrontendKind, HostEvent};
+    use super::super::interconnect::Interconnect;
+    use super::super::settings::Settings;
+
+    /// Returns a cpu without a window running the given rom.
+    fn cpu(rom: Vec<u8>) -> Cpu {
+        let mut settings = Settings::default();
+        settings.frontend = FrontendKind::Headless;
+        let interconnect = Interconnect::new(rom, &settings).unwrap();
+        Cpu::new(interconnect, &settings)
+    }
+
+    /// Runs a number of instructions.
+    fn run(cpu: &mut Cpu, steps: usize) {
+        for _ in 0..steps {
+            cpu.step();
+        }
+    }
+
+    #[test]
+    fn f000_loads_a_long_address_into_i() {
+        // i := long 0x1234, then v0 := 1.
+        let mut cpu = cpu(vec![0xF0, 0x00, 0x12, 0x34, 0x60, 0x01]);
+        run(&mut cpu, 1);
+        assert_eq!(cpu.i, 0x1234);
+        assert_eq!(cpu.pc(), 0x204);
+    }
+
+    #[test]
+    fn skips_step_over_all_of_f000() {
+        // if v0 != 0 then i := long 0x1234, then v1 := 1.
+        let mut cpu = cpu(vec![0x30, 0x00, 0xF0, 0x00, 0x12, 0x34, 0x61, 0x01]);
+        run(&mut cpu, 2);
+        assert_eq!(cpu.i, 0);
+        assert_eq!(cpu.v1, 1);
+    }
+
+    #[test]
+    fn save_and_load_ranges_leave_i_alone() {
+        // v1 := 1, v2 := 2, v3 := 3, i := 0x300, save v3 - v1, load v1 - v2.
+        let mut cpu = cpu(vec![0x61, 0x01, 0x62, 0x02, 0x63, 0x03, 0xA3, 0x00,
+                               0x53, 0x12, 0x51, 0x23]);
+        run(&mut cpu, 5);
+        assert_eq!(cpu.i, 0x300);
+        assert_eq!(cpu.interconnect.memory.read(0x300), 3);
+        assert_eq!(cpu.interconnect.memory.read(0x301), 2);
+        assert_eq!(cpu.interconnect.memory.read(0x302), 1);
+
+        run(&mut cpu, 1);
+        assert_eq!((cpu.v1, cpu.v2), (3, 2));
+    }
+
+    #[test]
+    fn exit_halts() {
+        let mut cpu = cpu(vec![0x00, 0xFD]);
+        run(&mut cpu, 1);
+        assert!(cpu.halted());
+    }
+
+    #[test]
+    fn fx0a_waits_for_a_key_to_be_released() {
+        // LD V1, K then loop forever.
+        let mut cpu = cpu(vec![0xF1, 0x0A, 0x12, 0x02]);
+
+        run(&mut cpu, 20);
+        assert_eq!(cpu.pc(), 0x200);
+
+        cpu.handle_event(HostEvent::Key(5, true));
+        cpu.step();
+        assert_eq!(cpu.pc(), 0x200);
+
+        cpu.handle_event(HostEvent::Key(5, false));
+        cpu.step();
+        assert_eq!(cpu.pc(), 0x202);
+        assert_eq!(cpu.v1, 5);
+    }
+
+    #[test]
+    fn fx0a_ignores_keys_held_before_it_started() {
+        let mut cpu = cpu(vec![0xF1, 0x0A, 0x12, 0x02]);
+        cpu.handle_event(HostEvent::Key(3, true));
+        cpu.step();
+
+        cpu.handle_event(HostEvent::Key(3, false));
+        cpu.step();
+        assert_eq!(cpu.pc(), 0x200);
+    }
+
+    #[test]
+    fn key_skips_use_the_low_nibble_of_vx() {
+        // v0 := 0x15, if key v0 is pressed skip, v1 := 1, then the same with
+        // sknp and v2.
+        let mut cpu = cpu(vec![0x60, 0x15, 0xE0, 0x9E, 0x61, 0x01, 0xE0, 0xA1, 0x62, 0x01]);
+        cpu.handle_event(HostEvent::Key(5, true));
+        run(&mut cpu, 4);
+        assert_eq!((cpu.v1, cpu.v2), (0, 1));
+        assert_eq!(cpu.pc(), 0x20A);
+    }
+}
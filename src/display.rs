@@ -0,0 +1,235 @@
+// Display size parameters for the standard CHIP-8 resolution.
+pub const DISPLAY_WIDTH: usize = 64;
+pub const DISPLAY_HEIGHT: usize = 32;
+
+// SCHIP's high resolution mode doubles the display in both directions.
+pub const HIRES_WIDTH: usize = 128;
+pub const HIRES_HEIGHT: usize = 64;
+
+pub struct Display {
+    // 64x32 buffer for the application to write to. The contents of this buffer
+    // is presented by the frontend once per frame.
+    pixels: Vec<u8>,
+
+    // Current resolution of the display, which may change at runtime.
+    width: usize,
+    height: usize,
+
+    // Set when the display has changed since it was last presented.
+    pub dirty: bool,
+}
+
+impl Display {
+    pub fn new() -> Display {
+        Display {
+            pixels: vec![0; DISPLAY_WIDTH * DISPLAY_HEIGHT],
+            width: DISPLAY_WIDTH,
+            height: DISPLAY_HEIGHT,
+            dirty: true,
+        }
+    }
+
+    /// Draws a sprite to the display. Sprites are 8 pixels wide, or 16 for
+    /// SCHIP's large sprites, with every row taking a byte for each 8 pixels.
+    /// Sprites that run off the edge of the display wrap around to the other
+    /// side, or are cut off when clipping. The starting position always wraps.
+    pub fn draw(&mut self, x: usize, y: usize, sprite: Vec<u8>, sprite_width: usize,
+                clip: bool) -> u8 {
+        let width = self.width;
+        let height = self.height;
+        let x = x % width;
+        let y = y % height;
+        let row_bytes = sprite_width / 8;
+        let mut collision: u8 = 0;
+
+        for i in 0..sprite.len() / row_bytes {
+            // Each row of the sprite draws on one line.
+            let row = y + i;
+            if clip && row >= height {
+                break;
+            }
+            let offset = (row % height) * width;
+
+            // Loop through the bits in the current row, most significant
+            // first, and set the display values based on them.
+            for j in 0..sprite_width {
+                let byte = sprite[i * row_bytes + j / 8];
+                let value = (byte >> (8 - 1 - j % 8)) & 0x01;
+                let column = x + j;
+                if clip && column >= width {
+                    break;
+                }
+                let index = offset + column % width;
+
+                // Save the previous state of the pixel before setting it for
+                // collision detection.
+                let prev = self.pixels[index];
+
+                // Draw the bit to the display.
+                self.pixels[index] = value ^ prev;
+
+                // Check the previous state of the pixel and check if it was
+                // erased, if so then there was a sprite collision.
+                if prev == 1 && self.pixels[index] == 0 {
+                    collision = 1;
+                }
+            }
+        }
+
+        // The display is presented at the end of the frame.
+        self.dirty = true;
+
+        collision
+    }
+
+    /// Moves the display down by a number of lines, blanking the lines at the
+    /// top.
+    pub fn scroll_down(&mut self, lines: usize) {
+        let shift = (lines * self.width).min(self.pixels.len());
+        let len = self.pixels.len();
+        for i in (shift..len).rev() {
+            self.pixels[i] = self.pixels[i - shift];
+        }
+        for i in 0..shift {
+            self.pixels[i] = 0;
+        }
+        self.dirty = true;
+    }
+
+    /// Moves the display up by a number of lines, blanking the lines at the
+    /// bottom.
+    pub fn scroll_up(&mut self, lines: usize) {
+        let shift = (lines * self.width).min(self.pixels.len());
+        let len = self.pixels.len();
+        for i in 0..len - shift {
+            self.pixels[i] = self.pixels[i + shift];
+        }
+        for i in len - shift..len {
+            self.pixels[i] = 0;
+        }
+        self.dirty = true;
+    }
+
+    /// Moves the display left by a number of pixels, blanking the columns on
+    /// the right.
+    pub fn scroll_left(&mut self, columns: usize) {
+        let width = self.width;
+        for row in self.pixels.chunks_mut(width) {
+            for x in 0..width {
+                row[x] = if x + columns < width { row[x + columns] } else { 0 };
+            }
+        }
+        self.dirty = true;
+    }
+
+    /// Moves the display right by a number of pixels, blanking the columns on
+    /// the left.
+    pub fn scroll_right(&mut self, columns: usize) {
+        let width = self.width;
+        for row in self.pixels.chunks_mut(width) {
+            for x in (0..width).rev() {
+                row[x] = if x >= columns { row[x - columns] } else { 0 };
+            }
+        }
+        self.dirty = true;
+    }
+
+    /// Clears all pixels on the display by setting them all to an off state.
+    pub fn clear(&mut self) {
+        for i in 0..self.pixels.len() {
+            self.pixels[i] = 0;
+        }
+        self.dirty = true;
+    }
+
+    /// Changes the resolution of the display, such as when switching to SCHIP
+    /// high resolution mode. The display is cleared in the process.
+    pub fn set_resolution(&mut self, width: usize, height: usize) {
+        if width == self.width && height == self.height {
+            return;
+        }
+
+        self.pixels = vec![0; width * height];
+        self.width = width;
+        self.height = height;
+        self.dirty = true;
+    }
+
+    /// Returns the value of every pixel row by row.
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Display, HIRES_HEIGHT, HIRES_WIDTH};
+
+    /// Returns the rows of the top left corner of the display as strings.
+    fn corner(display: &Display, width: usize, height: usize) -> Vec<String> {
+        (0..height).map(|y| {
+            (0..width).map(|x| {
+                if display.pixels()[y * display.width() + x] != 0 { '#' } else { '.' }
+            }).collect()
+        }).collect()
+    }
+
+    #[test]
+    fn draw_reads_two_bytes_a_row_for_large_sprites() {
+        let mut display = Display::new();
+        display.set_resolution(HIRES_WIDTH, HIRES_HEIGHT);
+        let mut sprite = vec![0; 32];
+        sprite[0] = 0x80;
+        sprite[1] = 0x01;
+        sprite[31] = 0x01;
+
+        assert_eq!(display.draw(0, 0, sprite.clone(), 16, false), 0);
+        assert_eq!(display.pixels()[0], 1);
+        assert_eq!(display.pixels()[15], 1);
+        assert_eq!(display.pixels()[15 * HIRES_WIDTH + 15], 1);
+        assert_eq!(display.pixels().iter().filter(|&&pixel| pixel != 0).count(), 3);
+
+        assert_eq!(display.draw(0, 0, sprite, 16, false), 1);
+        assert!(display.pixels().iter().all(|&pixel| pixel == 0));
+    }
+
+    #[test]
+    fn scroll_down_blanks_the_top_lines() {
+        let mut display = Display::new();
+        display.draw(0, 0, vec![0xC0, 0x80], 8, false);
+        display.scroll_down(2);
+        assert_eq!(corner(&display, 3, 4), vec!["...", "...", "##.", "#.."]);
+    }
+
+    #[test]
+    fn scroll_up_blanks_the_bottom_lines() {
+        let mut display = Display::new();
+        display.draw(0, 2, vec![0xC0, 0x80], 8, false);
+        display.scroll_up(1);
+        assert_eq!(corner(&display, 3, 4), vec!["...", "##.", "#..", "..."]);
+        assert!(display.pixels()[31 * 64..].iter().all(|&pixel| pixel == 0));
+    }
+
+    #[test]
+    fn scroll_left_and_right_move_every_row() {
+        let mut display = Display::new();
+        display.draw(4, 0, vec![0x80, 0x40], 8, false);
+        display.scroll_left(4);
+        assert_eq!(corner(&display, 3, 2), vec!["#..", ".#."]);
+
+        display.scroll_right(1);
+        assert_eq!(corner(&display, 3, 2), vec![".#.", "..#"]);
+
+        // Pixels pushed off the edge are lost.
+        display.scroll_left(2);
+        assert_eq!(corner(&display, 3, 2), vec!["...", "#.."]);
+    }
+}
@@ -0,0 +1,188 @@
+// How much brightness a pixel keeps each frame when no strength is given.
+const DEFAULT_PERSISTENCE: f32 = 0.6;
+
+// Brightness below this is treated as fully off.
+const MIN_LEVEL: f32 = 0.02;
+
+/// Filters applied to the display before it is presented to reduce the flicker
+/// caused by games erasing and redrawing sprites with XOR.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Filter {
+    // The display is shown as is.
+    None,
+
+    // Pixels that switch off fade out over several frames like the phosphor
+    // of a CRT. The value is the fraction of brightness kept each frame.
+    Decay(f32),
+
+    // Pixels lit in either of the last two frames are shown as lit.
+    Or,
+}
+
+impl Filter {
+    /// Parses a filter from "none", "or" or "decay" with an optional strength
+    /// between 0 and 1 such as "decay:0.8".
+    pub fn parse(spec: &str) -> Result<Filter, String> {
+        let mut parts = spec.trim().splitn(2, ':');
+        let name = parts.next().unwrap_or("");
+        let strength = parts.next();
+
+        match (name, strength) {
+            ("none", None) => Ok(Filter::None),
+            ("or", None) => Ok(Filter::Or),
+            ("decay", None) => Ok(Filter::Decay(DEFAULT_PERSISTENCE)),
+            ("decay", Some(strength)) => {
+                match strength.trim().parse::<f32>() {
+                    Ok(value) if value >= 0.0 && value < 1.0 => Ok(Filter::Decay(value)),
+                    _ => Err(format!("invalid filter strength '{}', expected a number \
+                                      from 0 up to 1", strength)),
+                }
+            },
+            _ => Err(format!("invalid filter '{}', expected none, or, or decay[:STRENGTH]", spec)),
+        }
+    }
+}
+
+impl Default for Filter {
+    fn default() -> Filter {
+        Filter::None
+    }
+}
+
+pub struct DisplayFilter {
+    filter: Filter,
+
+    // Brightness of every pixel from 0 (off) to 1 (fully lit).
+    levels: Vec<f32>,
+
+    // The last non-zero value of every pixel, used to pick the colour of
+    // pixels that are fading out.
+    values: Vec<u8>,
+
+    // The display as it was during the previous frame.
+    previous: Vec<u8>,
+}
+
+impl DisplayFilter {
+    pub fn new(filter: Filter, size: usize) -> DisplayFilter {
+        DisplayFilter {
+            filter: filter,
+            levels: vec![0.0; size],
+            values: vec![0; size],
+            previous: vec![0; size],
+        }
+    }
+
+    /// Returns true if the filter changes the display, in which case the
+    /// output may change between frames even if the display does not.
+    pub fn is_active(&self) -> bool {
+        self.filter != Filter::None
+    }
+
+    /// Runs the filter over the display for a new frame.
+    pub fn apply(&mut self, display: &[u8]) {
+        // Resolution changes throw away the history.
+        if display.len() != self.levels.len() {
+            *self = DisplayFilter::new(self.filter, display.len());
+        }
+
+        for i in 0..display.len() {
+            let value = display[i];
+            if value != 0 {
+                self.values[i] = value;
+            }
+
+            self.levels[i] = match self.filter {
+                Filter::None => if value != 0 { 1.0 } else { 0.0 },
+                Filter::Or => {
+                    if value == 0 && self.previous[i] != 0 {
+                        self.values[i] = self.previous[i];
+                    }
+                    if value != 0 || self.previous[i] != 0 { 1.0 } else { 0.0 }
+                },
+                Filter::Decay(persistence) => {
+                    let level = self.levels[i] * persistence;
+                    if value != 0 {
+                        1.0
+                    } else if level < MIN_LEVEL {
+                        0.0
+                    } else {
+                        level
+                    }
+                },
+            };
+
+            self.previous[i] = value;
+        }
+    }
+
+    /// Returns the pixel value and brightness of a pixel after filtering.
+    #[inline(always)]
+    pub fn pixel(&self, index: usize) -> (u8, f32) {
+        (self.values[index], self.levels[index])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DisplayFilter, Filter};
+
+    #[test]
+    fn parse_reads_names_and_strengths() {
+        assert_eq!(Filter::parse("none"), Ok(Filter::None));
+        assert_eq!(Filter::parse("or"), Ok(Filter::Or));
+        assert_eq!(Filter::parse("decay:0.5"), Ok(Filter::Decay(0.5)));
+        assert!(Filter::parse("decay:1").is_err());
+        assert!(Filter::parse("or:0.5").is_err());
+    }
+
+    #[test]
+    fn decay_fades_pixels_out() {
+        let mut filter = DisplayFilter::new(Filter::Decay(0.5), 2);
+        filter.apply(&[2, 0]);
+        assert_eq!(filter.pixel(0), (2, 1.0));
+        assert_eq!(filter.pixel(1), (0, 0.0));
+
+        filter.apply(&[0, 0]);
+        assert_eq!(filter.pixel(0), (2, 0.5));
+        filter.apply(&[0, 0]);
+        assert_eq!(filter.pixel(0), (2, 0.25));
+
+        // Relighting a pixel brings it back to full brightness.
+        filter.apply(&[1, 0]);
+        assert_eq!(filter.pixel(0), (1, 1.0));
+    }
+
+    #[test]
+    fn decay_switches_dim_pixels_off() {
+        let mut filter = DisplayFilter::new(Filter::Decay(0.1), 1);
+        filter.apply(&[1]);
+        filter.apply(&[0]);
+        assert_eq!(filter.pixel(0).1, 0.1);
+        filter.apply(&[0]);
+        assert_eq!(filter.pixel(0).1, 0.0);
+    }
+
+    #[test]
+    fn or_keeps_pixels_lit_in_the_previous_frame() {
+        let mut filter = DisplayFilter::new(Filter::Or, 2);
+        filter.apply(&[3, 0]);
+        filter.apply(&[0, 1]);
+        assert_eq!(filter.pixel(0), (3, 1.0));
+        assert_eq!(filter.pixel(1), (1, 1.0));
+
+        filter.apply(&[0, 0]);
+        assert_eq!(filter.pixel(0).1, 0.0);
+        assert_eq!(filter.pixel(1).1, 1.0);
+    }
+
+    #[test]
+    fn apply_forgets_history_when_the_size_changes() {
+        let mut filter = DisplayFilter::new(Filter::Or, 2);
+        filter.apply(&[1, 1]);
+        filter.apply(&[0, 0, 0, 0]);
+        for i in 0..4 {
+            assert_eq!(filter.pixel(i), (0, 0.0));
+        }
+    }
+}
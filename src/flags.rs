@@ -0,0 +1,131 @@
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+// SCHIP saves V0 to V7 to the HP-48's RPL user flags. XO-CHIP extends this to
+// all 16 registers.
+pub const FLAG_COUNT: usize = 16;
+
+/// The RPL user flags SCHIP programs save registers to with FX75 and restore
+/// with FX85, usually to keep high scores. The flags are kept in a file for
+/// each rom, named after the SHA-1 of the rom it was loaded with, so they
+/// survive restarting notch like battery backed saves.
+pub struct RplFlags {
+    flags: [u8; FLAG_COUNT],
+
+    // File the flags are saved to. Flags are only kept in memory without one.
+    path: Option<PathBuf>,
+}
+
+impl RplFlags {
+    /// Loads the flags saved in a file. Flags that were never saved are zero.
+    pub fn load(path: Option<PathBuf>) -> RplFlags {
+        let mut flags = [0; FLAG_COUNT];
+
+        if let Some(ref path) = path {
+            let mut contents = Vec::new();
+            if let Ok(mut file) = File::open(path) {
+                match file.read_to_end(&mut contents) {
+                    Ok(_) => {
+                        for (flag, &value) in flags.iter_mut().zip(contents.iter()) {
+                            *flag = value;
+                        }
+                    },
+                    Err(why) => println!("notch: cannot read flags from '{}': {}",
+                                         path.display(), why),
+                }
+            }
+        }
+
+        RplFlags {
+            flags: flags,
+            path: path,
+        }
+    }
+
+    /// Stores values in the first flags and writes all of them to the file
+    /// if any changed, since some programs save their flags every frame.
+    /// Failing to write is reported but does not stop the program.
+    pub fn save(&mut self, values: &[u8]) {
+        let count = values.len().min(FLAG_COUNT);
+        if self.flags[..count] == values[..count] {
+            return;
+        }
+        self.flags[..count].copy_from_slice(&values[..count]);
+
+        if let Some(ref path) = self.path {
+            if let Err(why) = write_flags(path, &self.flags) {
+                println!("notch: cannot save flags to '{}': {}", path.display(), why);
+            }
+        }
+    }
+
+    pub fn get(&self, flag: usize) -> u8 {
+        self.flags[flag]
+    }
+}
+
+fn write_flags(path: &Path, flags: &[u8]) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        try!(fs::create_dir_all(dir));
+    }
+    let mut file = try!(File::create(path));
+    file.write_all(flags)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs::{self, File};
+    use std::io::Read;
+    use std::path::{Path, PathBuf};
+    use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+
+    use super::super::time;
+
+    use super::RplFlags;
+
+    static NEXT_FILE: AtomicUsize = ATOMIC_USIZE_INIT;
+
+    /// A flags file of its own for each test and each run of the tests.
+    fn flags_path(name: &str) -> PathBuf {
+        let unique = format!("{}-{}", time::precise_time_ns(), NEXT_FILE.fetch_add(1, Ordering::SeqCst));
+        env::temp_dir().join(format!("notch-test-{}-{}.flags", name, unique))
+    }
+
+    fn read_file(path: &Path) -> Vec<u8> {
+        let mut contents = Vec::new();
+        File::open(path).unwrap().read_to_end(&mut contents).unwrap();
+        contents
+    }
+
+    #[test]
+    fn saved_flags_are_loaded_again() {
+        let path = flags_path("reload");
+        RplFlags::load(Some(path.clone())).save(&[1, 2, 3]);
+
+        let flags = RplFlags::load(Some(path.clone()));
+        assert_eq!((flags.get(0), flags.get(1), flags.get(2), flags.get(3)), (1, 2, 3, 0));
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn unchanged_flags_are_not_written() {
+        let path = flags_path("unchanged");
+        let mut flags = RplFlags::load(Some(path.clone()));
+
+        // Saving the values the flags already hold leaves the file alone.
+        flags.save(&[0, 0]);
+        assert!(!path.exists());
+
+        flags.save(&[7, 0]);
+        assert!(path.exists());
+        fs::remove_file(&path).unwrap();
+        flags.save(&[7, 0]);
+        assert!(!path.exists());
+
+        flags.save(&[7, 1]);
+        assert_eq!(read_file(&path)[..3], [7, 1, 0]);
+        let _ = fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,177 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+// Font size constants.
+pub const CHARACTER_SIZE: usize = 5;
+pub const CHARACTER_COUNT: usize = 16;
+
+// Size of a font in memory and in font files.
+pub const FONT_SIZE: usize = CHARACTER_SIZE * CHARACTER_COUNT;
+
+// The characters 0-F of the fonts built into various interpreters. Each row
+// of a character is stored in the high 4 bits of a byte.
+const FONTS: [(&'static str, [[u8; CHARACTER_SIZE]; CHARACTER_COUNT]); 5] = [
+    // SCHIP 1.1's small font, which most modern interpreters copied.
+    ("schip", [
+        [0xF0, 0x90, 0x90, 0x90, 0xF0], // 0
+        [0x20, 0x60, 0x20, 0x20, 0x70], // 1
+        [0xF0, 0x10, 0xF0, 0x80, 0xF0], // 2
+        [0xF0, 0x10, 0xF0, 0x10, 0xF0], // 3
+        [0x90, 0x90, 0xF0, 0x10, 0x10], // 4
+        [0xF0, 0x80, 0xF0, 0x10, 0xF0], // 5
+        [0xF0, 0x80, 0xF0, 0x90, 0xF0], // 6
+        [0xF0, 0x10, 0x20, 0x40, 0x40], // 7
+        [0xF0, 0x90, 0xF0, 0x90, 0xF0], // 8
+        [0xF0, 0x90, 0xF0, 0x10, 0xF0], // 9
+        [0xF0, 0x90, 0xF0, 0x90, 0x90], // A
+        [0xE0, 0x90, 0xE0, 0x90, 0xE0], // B
+        [0xF0, 0x80, 0x80, 0x80, 0xF0], // C
+        [0xE0, 0x90, 0x90, 0x90, 0xE0], // D
+        [0xF0, 0x80, 0xF0, 0x80, 0xF0], // E
+        [0xF0, 0x80, 0xF0, 0x80, 0x80], // F
+    ]),
+
+    // The original interpreter on the COSMAC VIP.
+    ("vip", [
+        [0xF0, 0x90, 0x90, 0x90, 0xF0], // 0
+        [0x60, 0x20, 0x20, 0x20, 0x70], // 1
+        [0xF0, 0x10, 0xF0, 0x80, 0xF0], // 2
+        [0xF0, 0x10, 0xF0, 0x10, 0xF0], // 3
+        [0xA0, 0xA0, 0xF0, 0x20, 0x20], // 4
+        [0xF0, 0x80, 0xF0, 0x10, 0xF0], // 5
+        [0xF0, 0x80, 0xF0, 0x90, 0xF0], // 6
+        [0xF0, 0x10, 0x10, 0x10, 0x10], // 7
+        [0xF0, 0x90, 0xF0, 0x90, 0xF0], // 8
+        [0xF0, 0x90, 0xF0, 0x10, 0xF0], // 9
+        [0xF0, 0x90, 0xF0, 0x90, 0x90], // A
+        [0xF0, 0x50, 0x70, 0x50, 0xF0], // B
+        [0xF0, 0x80, 0x80, 0x80, 0xF0], // C
+        [0xF0, 0x50, 0x50, 0x50, 0xF0], // D
+        [0xF0, 0x80, 0xF0, 0x80, 0xF0], // E
+        [0xF0, 0x80, 0xF0, 0x80, 0x80], // F
+    ]),
+
+    // CHIPOS on the DREAM 6800, three pixels wide.
+    ("dream6800", [
+        [0xE0, 0xA0, 0xA0, 0xA0, 0xE0], // 0
+        [0x40, 0x40, 0x40, 0x40, 0x40], // 1
+        [0xE0, 0x20, 0xE0, 0x80, 0xE0], // 2
+        [0xE0, 0x20, 0xE0, 0x20, 0xE0], // 3
+        [0x80, 0xA0, 0xA0, 0xE0, 0x20], // 4
+        [0xE0, 0x80, 0xE0, 0x20, 0xE0], // 5
+        [0xE0, 0x80, 0xE0, 0xA0, 0xE0], // 6
+        [0xE0, 0x20, 0x20, 0x20, 0x20], // 7
+        [0xE0, 0xA0, 0xE0, 0xA0, 0xE0], // 8
+        [0xE0, 0xA0, 0xE0, 0x20, 0xE0], // 9
+        [0xE0, 0xA0, 0xE0, 0xA0, 0xA0], // A
+        [0xC0, 0xA0, 0xE0, 0xA0, 0xC0], // B
+        [0xE0, 0x80, 0x80, 0x80, 0xE0], // C
+        [0xC0, 0xA0, 0xA0, 0xA0, 0xC0], // D
+        [0xE0, 0x80, 0xE0, 0x80, 0xE0], // E
+        [0xE0, 0x80, 0xC0, 0x80, 0x80], // F
+    ]),
+
+    // The ETI-660, also three pixels wide.
+    ("eti660", [
+        [0xE0, 0xA0, 0xA0, 0xA0, 0xE0], // 0
+        [0x20, 0x20, 0x20, 0x20, 0x20], // 1
+        [0xE0, 0x20, 0xE0, 0x80, 0xE0], // 2
+        [0xE0, 0x20, 0xE0, 0x20, 0xE0], // 3
+        [0xA0, 0xA0, 0xE0, 0x20, 0x20], // 4
+        [0xE0, 0x80, 0xE0, 0x20, 0xE0], // 5
+        [0xE0, 0x80, 0xE0, 0xA0, 0xE0], // 6
+        [0xE0, 0x20, 0x20, 0x20, 0x20], // 7
+        [0xE0, 0xA0, 0xE0, 0xA0, 0xE0], // 8
+        [0xE0, 0xA0, 0xE0, 0x20, 0xE0], // 9
+        [0xE0, 0xA0, 0xE0, 0xA0, 0xA0], // A
+        [0x80, 0x80, 0xE0, 0xA0, 0xE0], // B
+        [0xE0, 0x80, 0x80, 0x80, 0xE0], // C
+        [0x20, 0x20, 0xE0, 0xA0, 0xE0], // D
+        [0xE0, 0x80, 0xE0, 0x80, 0xE0], // E
+        [0xE0, 0x80, 0xC0, 0x80, 0x80], // F
+    ]),
+
+    // Fish'N'Chips, with rounded characters.
+    ("fish", [
+        [0x60, 0xA0, 0xA0, 0xA0, 0xC0], // 0
+        [0x40, 0xC0, 0x40, 0x40, 0xE0], // 1
+        [0xC0, 0x20, 0x40, 0x80, 0xE0], // 2
+        [0xC0, 0x20, 0x40, 0x20, 0xC0], // 3
+        [0x20, 0xA0, 0xE0, 0x20, 0x20], // 4
+        [0xE0, 0x80, 0xC0, 0x20, 0xC0], // 5
+        [0x40, 0x80, 0xC0, 0xA0, 0x40], // 6
+        [0xE0, 0x20, 0x60, 0x40, 0x40], // 7
+        [0x40, 0xA0, 0x40, 0xA0, 0x40], // 8
+        [0x40, 0xA0, 0x60, 0x20, 0x40], // 9
+        [0x40, 0xA0, 0xE0, 0xA0, 0xA0], // A
+        [0xC0, 0xA0, 0xC0, 0xA0, 0xC0], // B
+        [0x60, 0x80, 0x80, 0x80, 0x60], // C
+        [0xC0, 0xA0, 0xA0, 0xA0, 0xC0], // D
+        [0xE0, 0x80, 0xC0, 0x80, 0xE0], // E
+        [0xE0, 0x80, 0xC0, 0x80, 0x80], // F
+    ]),
+];
+
+/// The hex digits programs draw with FX29, copied into reserved memory.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Font {
+    pub glyphs: [[u8; CHARACTER_SIZE]; CHARACTER_COUNT],
+}
+
+impl Font {
+    /// Looks up a built-in font by name.
+    pub fn builtin(name: &str) -> Option<Font> {
+        FONTS.iter()
+            .find(|&&(font, _)| font == name)
+            .map(|&(_, glyphs)| Font { glyphs: glyphs })
+    }
+
+    /// Returns the names of all built-in fonts.
+    pub fn names() -> Vec<&'static str> {
+        FONTS.iter().map(|&(name, _)| name).collect()
+    }
+
+    /// Parses either the name of a built-in font or the path of a font file.
+    pub fn parse(spec: &str) -> Result<Font, String> {
+        match Font::builtin(spec.trim()) {
+            Some(font) => Ok(font),
+            None => Font::load(spec.trim()),
+        }
+    }
+
+    /// Reads a font file, which holds the five rows of each character from 0
+    /// to F one after another, 80 bytes in all.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Font, String> {
+        let filename = format!("{}", path.as_ref().display());
+        let mut contents = Vec::new();
+
+        match File::open(path) {
+            Ok(ref mut file) => {
+                if let Err(why) = file.read_to_end(&mut contents) {
+                    return Err(format!("cannot read font '{}': {}", filename, why));
+                }
+            },
+            Err(why) => {
+                return Err(format!("invalid font '{}', expected one of {} or a font file: {}",
+                                   filename, Font::names().join(", "), why));
+            },
+        }
+        if contents.len() != FONT_SIZE {
+            return Err(format!("font '{}' is {} bytes, expected {}",
+                               filename, contents.len(), FONT_SIZE));
+        }
+
+        let mut font = Font { glyphs: [[0; CHARACTER_SIZE]; CHARACTER_COUNT] };
+        for (glyph, rows) in font.glyphs.iter_mut().zip(contents.chunks(CHARACTER_SIZE)) {
+            glyph.copy_from_slice(rows);
+        }
+        Ok(font)
+    }
+}
+
+impl Default for Font {
+    fn default() -> Font {
+        Font::builtin("schip").unwrap()
+    }
+}
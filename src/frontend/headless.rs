@@ -0,0 +1,21 @@
+use super::{Frontend, HostEvent};
+use super::super::display::Display;
+
+/// A frontend without a window, audio or input. The virtual machine runs as
+/// fast as possible which is useful for capturing screenshots and recordings
+/// from scripts.
+pub struct Headless;
+
+impl Frontend for Headless {
+    fn present(&mut self, _: &Display, _: &[bool; 16]) {}
+
+    fn poll_events(&mut self) -> Vec<HostEvent> {
+        Vec::new()
+    }
+
+    fn set_beeping(&mut self, _: u64, _: bool) {}
+
+    fn is_realtime(&self) -> bool {
+        false
+    }
+}
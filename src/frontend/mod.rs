@@ -0,0 +1,113 @@
+use super::display::Display;
+
+pub mod headless;
+pub mod sdl;
+#[cfg(unix)]
+pub mod terminal;
+
+/// Which frontend the virtual machine runs with.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FrontendKind {
+    // An SDL window with audio.
+    Sdl,
+
+    // Unicode text in the terminal the virtual machine was started from.
+    Terminal(Glyphs),
+
+    // No window, audio or input.
+    Headless,
+}
+
+/// Characters used to draw the display in a terminal.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Glyphs {
+    // Upper half blocks with separate foreground and background colours give
+    // two pixels per character, 64x16 characters for the CHIP-8 display.
+    HalfBlock,
+
+    // Braille patterns give eight pixels per character, 32x8 characters for
+    // the CHIP-8 display, but only two colours.
+    Braille,
+}
+
+impl Glyphs {
+    pub fn parse(name: &str) -> Result<Glyphs, String> {
+        match name.trim() {
+            "half" => Ok(Glyphs::HalfBlock),
+            "braille" => Ok(Glyphs::Braille),
+            _ => Err(format!("invalid glyphs '{}', expected half or braille", name)),
+        }
+    }
+}
+
+/// Events from the host that the virtual machine reacts to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HostEvent {
+    // A key on the CHIP-8 keypad was pressed (true) or released (false).
+    Key(u8, bool),
+
+    // The user asked for a screenshot of the display.
+    Screenshot,
+
+    // The user paused or resumed the virtual machine.
+    Pause,
+
+    // The user changed a byte of memory while paused.
+    Poke(u16, u8),
+
+    // The user muted or unmuted the beep.
+    Mute,
+
+    // The window was closed.
+    Quit,
+}
+
+/// The registers of the cpu that point into memory, for frontends that show
+/// memory.
+pub struct CpuState {
+    pub pc: u16,
+    pub i: u16,
+
+    // Addresses of the calls on the stack, oldest first.
+    pub stack: Vec<u16>,
+}
+
+/// The host side of the virtual machine. A frontend presents the display,
+/// plays the beep and reports input.
+pub trait Frontend {
+    /// Shows the display and which keys of the keypad are held down. This is
+    /// called once at the end of every frame.
+    fn present(&mut self, display: &Display, keys: &[bool; 16]);
+
+    /// Shows the contents of memory and where the cpu points into it. This is
+    /// called before every present, including while the virtual machine is
+    /// paused.
+    fn inspect(&mut self, _memory: &[u8], _cpu: &CpuState, _paused: bool) {}
+
+    /// Collects the events that happened since the last poll.
+    fn poll_events(&mut self) -> Vec<HostEvent>;
+
+    /// Starts or stops the beep from the start of an emulated frame.
+    fn set_beeping(&mut self, frame: u64, beeping: bool);
+
+    /// Stops the beep and counts emulated frames from 0 again, after the
+    /// virtual machine is reset.
+    fn restart_beep(&mut self) {
+        self.set_beeping(0, false);
+    }
+
+    /// Silences the beep or brings it back.
+    fn set_muted(&mut self, _muted: bool) {}
+
+    /// Returns true if the virtual machine should be throttled to run at real
+    /// time speed for a human to follow along.
+    fn is_realtime(&self) -> bool {
+        true
+    }
+
+    /// Returns true if the user can pause and resume the virtual machine
+    /// from the frontend.
+    fn can_pause(&self) -> bool {
+        false
+    }
+}
@@ -0,0 +1,329 @@
+use super::super::sdl2::controller::{Axis, GameController};
+use super::super::sdl2::event::{Event, WindowEventId};
+use super::super::sdl2::keyboard::Keycode;
+use super::super::sdl2::mouse::Mouse;
+use super::super::sdl2;
+
+use super::{CpuState, Frontend, HostEvent};
+use super::super::display::Display;
+use super::super::graphics::Graphics;
+use super::super::keymap::Keymap;
+use super::super::memview::MemoryViewer;
+use super::super::settings::Settings;
+use super::super::sound::Sound;
+
+/// The default frontend which draws to an SDL window and plays audio through
+/// SDL.
+pub struct SdlFrontend {
+    // Graphics manages drawing with SDL.
+    graphics: Graphics,
+
+    // A second window with a hex dump of memory, hidden until toggled.
+    viewer: MemoryViewer,
+
+    // Set after a frame of either window fails to draw so the error is only
+    // reported once until drawing works again.
+    draw_failed: bool,
+    inspect_failed: bool,
+
+    // SDL object used to collect input events.
+    event_pump: sdl2::EventPump,
+
+    // Sounds handles sound output through SDL.
+    sound: Sound,
+
+    // Translates keys on the keyboard and controller buttons to the CHIP-8
+    // keypad.
+    keymap: Keymap,
+
+    // Game controllers are opened as they are plugged in. Without the
+    // subsystem only the keyboard is used.
+    controller_subsystem: Option<sdl2::GameControllerSubsystem>,
+    controllers: Vec<GameController>,
+
+    // Controller sticks and triggers pushed far enough to count as held
+    // buttons, by host key name.
+    held_axes: Vec<String>,
+
+    // Keys of the on-screen keypad held down by the mouse and by each finger
+    // touching the screen.
+    mouse_key: Option<u8>,
+    touch_keys: Vec<(i64, u8)>,
+}
+
+// Mouse events SDL generates from touches carry this id. They are ignored
+// since the touches themselves are handled.
+const TOUCH_MOUSE_ID: u32 = 0xFFFFFFFF;
+
+// How far a stick or trigger has to move from rest before it presses a key,
+// out of 32767.
+const AXIS_THRESHOLD: i16 = 16384;
+
+impl SdlFrontend {
+    pub fn new(settings: &Settings) -> sdl2::SdlResult<SdlFrontend> {
+        // Setup SDL for graphics and audio.
+        let sdl_context = try!(sdl2::init());
+        let graphics = try!(Graphics::new(&sdl_context, settings));
+        let viewer = try!(MemoryViewer::new(&sdl_context, settings));
+        let event_pump = try!(sdl_context.event_pump());
+
+        // Carry on without sound rather than refusing to run when there is no
+        // audio device.
+        let sound = if settings.audio {
+            match Sound::new(&sdl_context, settings.tone) {
+                Ok(sound) => sound,
+                Err(why) => {
+                    println!("notch: audio disabled: {}", why);
+                    Sound::silent()
+                },
+            }
+        } else {
+            Sound::silent()
+        };
+
+        // Controllers already plugged in are reported as added devices on the
+        // first poll.
+        let controller_subsystem = match sdl_context.game_controller() {
+            Ok(subsystem) => Some(subsystem),
+            Err(why) => {
+                println!("notch: game controllers disabled: {}", why);
+                None
+            },
+        };
+
+        Ok(SdlFrontend {
+            graphics: graphics,
+            viewer: viewer,
+            draw_failed: false,
+            inspect_failed: false,
+            event_pump: event_pump,
+            sound: sound,
+            keymap: settings.keymap.clone(),
+            controller_subsystem: controller_subsystem,
+            controllers: Vec::new(),
+            held_axes: Vec::new(),
+            mouse_key: None,
+            touch_keys: Vec::new(),
+        })
+    }
+
+    /// Opens a newly plugged in game controller.
+    fn add_controller(&mut self, index: u32) {
+        if let Some(ref subsystem) = self.controller_subsystem {
+            if !subsystem.is_game_controller(index) {
+                return;
+            }
+            match subsystem.open(index) {
+                Ok(controller) => {
+                    println!("Controller connected: {}", controller.name());
+                    self.controllers.push(controller);
+                },
+                Err(why) => println!("notch: cannot open controller: {}", why),
+            }
+        }
+    }
+
+    /// Translates the position of a stick or trigger into presses of the
+    /// host keys "pad leftx-" and "pad leftx+" for sticks and plain
+    /// "pad lefttrigger" for triggers.
+    fn handle_axis(&mut self, axis: Axis, value: i16, host_events: &mut Vec<HostEvent>) {
+        let name = format!("pad {}", axis.string());
+
+        match axis {
+            Axis::TriggerLeft | Axis::TriggerRight => {
+                self.set_axis_held(name, value > AXIS_THRESHOLD, host_events);
+            },
+            _ => {
+                self.set_axis_held(format!("{}-", name), value < -AXIS_THRESHOLD, host_events);
+                self.set_axis_held(format!("{}+", name), value > AXIS_THRESHOLD, host_events);
+            },
+        }
+    }
+
+    fn set_axis_held(&mut self, host_key: String, held: bool, host_events: &mut Vec<HostEvent>) {
+        let position = self.held_axes.iter().position(|held_key| *held_key == host_key);
+        match (position, held) {
+            (None, true) => self.held_axes.push(host_key.clone()),
+            (Some(position), false) => { self.held_axes.remove(position); },
+            _ => return,
+        }
+
+        if let Some(key) = self.keymap.get(&host_key) {
+            host_events.push(HostEvent::Key(key, held));
+        }
+    }
+}
+
+impl Frontend for SdlFrontend {
+    fn present(&mut self, display: &Display, keys: &[bool; 16]) {
+        let result = self.graphics.present(display, keys);
+        report_draw(result, "the display", &mut self.draw_failed);
+    }
+
+    fn inspect(&mut self, memory: &[u8], cpu: &CpuState, paused: bool) {
+        let result = self.viewer.present(memory, cpu, paused);
+        report_draw(result, "memory", &mut self.inspect_failed);
+    }
+
+    /// Get input events from SDL and translate them into host events.
+    fn poll_events(&mut self) -> Vec<HostEvent> {
+        // Collect the events from the iterator ahead of time so we are not
+        // borrowing when we need to toggle fullscreen.
+        let events: Vec<Event> = self.event_pump.poll_iter().collect();
+        let mut host_events = Vec::new();
+        let viewer_id = self.viewer.window_id();
+
+        for event in events {
+            match event {
+                // Closing the memory viewer only hides it. With two windows
+                // SDL only sends a quit event once both are closed, so closing
+                // the main window quits by itself.
+                Event::Window { window_id, win_event_id: WindowEventId::Close, .. } => {
+                    if Some(window_id) == viewer_id {
+                        self.viewer.toggle();
+                    } else {
+                        host_events.push(HostEvent::Quit);
+                    }
+                },
+
+                // Keys and the mouse in the memory viewer navigate and edit
+                // memory rather than pressing keypad keys. Releases still go
+                // to the keypad so keys held when focus moves are let go.
+                Event::KeyDown { window_id, keycode: Some(keycode), .. }
+                        if Some(window_id) == viewer_id &&
+                           keycode != Keycode::F8 && keycode != Keycode::F9 => {
+                    self.viewer.key_down(keycode, &mut host_events);
+                },
+                Event::MouseButtonDown { window_id, mouse_btn: Mouse::Left, x, y, .. }
+                        if Some(window_id) == viewer_id => {
+                    self.viewer.click(x, y);
+                },
+                Event::MouseWheel { window_id, y, .. } if Some(window_id) == viewer_id => {
+                    self.viewer.scroll(-y * 4);
+                },
+
+                Event::Quit {..} => {
+                    // Detect close button or escape button events.
+                    // The interpreter is then signaled to halt and stop
+                    // executing code when the cpu reads this value.
+                    host_events.push(HostEvent::Quit);
+                },
+                Event::KeyDown { keycode: Some(Keycode::F11), repeat: false, .. } => {
+                    self.graphics.toggle_fullscreen();
+                },
+                Event::KeyDown { keycode: Some(Keycode::F7), repeat: false, .. } => {
+                    host_events.push(HostEvent::Mute);
+                },
+                Event::KeyDown { keycode: Some(Keycode::F8), repeat: false, .. } => {
+                    self.viewer.toggle();
+                },
+                Event::KeyDown { keycode: Some(Keycode::F9), repeat: false, .. } => {
+                    host_events.push(HostEvent::Pause);
+                },
+                Event::KeyDown { keycode: Some(Keycode::F10), repeat: false, .. } => {
+                    self.graphics.toggle_keypad();
+                },
+                Event::KeyDown { keycode: Some(Keycode::F12), repeat: false, .. } => {
+                    host_events.push(HostEvent::Screenshot);
+                },
+
+                // Translate keys on the keyboard to the CHIP-8 keypad.
+                Event::KeyDown { keycode: Some(keycode), repeat: false, .. } => {
+                    if let Some(key) = self.keymap.get(&keycode.name()) {
+                        host_events.push(HostEvent::Key(key, true));
+                    }
+                },
+                Event::KeyUp { keycode: Some(keycode), .. } => {
+                    if let Some(key) = self.keymap.get(&keycode.name()) {
+                        host_events.push(HostEvent::Key(key, false));
+                    }
+                },
+
+                // Controller buttons are bound like keys named "pad a",
+                // "pad dpup" and so on.
+                Event::ControllerButtonDown { button, .. } => {
+                    if let Some(key) = self.keymap.get(&format!("pad {}", button.string())) {
+                        host_events.push(HostEvent::Key(key, true));
+                    }
+                },
+                Event::ControllerButtonUp { button, .. } => {
+                    if let Some(key) = self.keymap.get(&format!("pad {}", button.string())) {
+                        host_events.push(HostEvent::Key(key, false));
+                    }
+                },
+                Event::ControllerAxisMotion { axis, value, .. } => {
+                    self.handle_axis(axis, value, &mut host_events);
+                },
+                Event::ControllerDeviceAdded { which, .. } => {
+                    self.add_controller(which as u32);
+                },
+                Event::ControllerDeviceRemoved { .. } => {
+                    self.controllers.retain(|controller| controller.attached());
+                },
+
+                // Clicks and touches on the on-screen keypad.
+                Event::MouseButtonDown { mouse_btn: Mouse::Left, which, x, y, .. } => {
+                    if which != TOUCH_MOUSE_ID {
+                        self.mouse_key = self.graphics.keypad_key_at(x, y);
+                        if let Some(key) = self.mouse_key {
+                            host_events.push(HostEvent::Key(key, true));
+                        }
+                    }
+                },
+                Event::MouseButtonUp { mouse_btn: Mouse::Left, .. } => {
+                    if let Some(key) = self.mouse_key.take() {
+                        host_events.push(HostEvent::Key(key, false));
+                    }
+                },
+                Event::FingerDown { finger_id, x, y, .. } => {
+                    // Touch positions are fractions of the window size.
+                    let (width, height) = self.graphics.output_size();
+                    let x = (x * width as f32) as i32;
+                    let y = (y * height as f32) as i32;
+                    if let Some(key) = self.graphics.keypad_key_at(x, y) {
+                        self.touch_keys.push((finger_id, key));
+                        host_events.push(HostEvent::Key(key, true));
+                    }
+                },
+                Event::FingerUp { finger_id, .. } => {
+                    if let Some(index) = self.touch_keys.iter().position(|&(id, _)| id == finger_id) {
+                        let (_, key) = self.touch_keys.remove(index);
+                        host_events.push(HostEvent::Key(key, false));
+                    }
+                },
+                _ => {}
+            }
+        }
+
+        host_events
+    }
+
+    fn set_beeping(&mut self, frame: u64, beeping: bool) {
+        self.sound.set_beeping(frame, beeping);
+    }
+
+    fn restart_beep(&mut self) {
+        self.sound.restart();
+    }
+
+    fn set_muted(&mut self, muted: bool) {
+        self.sound.set_muted(muted);
+    }
+
+    fn can_pause(&self) -> bool {
+        true
+    }
+}
+
+/// Reports a window that failed to draw, once until it draws again.
+fn report_draw(result: sdl2::SdlResult<()>, what: &str, failed: &mut bool) {
+    match result {
+        Ok(_) => *failed = false,
+        Err(why) => {
+            if !*failed {
+                println!("notch: cannot draw {}: {}", what, why);
+            }
+            *failed = true;
+        },
+    }
+}
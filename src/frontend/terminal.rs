@@ -0,0 +1,314 @@
+use std::io::{self, Write};
+use std::mem;
+
+use super::super::libc;
+use super::super::time;
+
+use super::{Frontend, Glyphs, HostEvent};
+use super::super::display::Display;
+use super::super::keymap::Keymap;
+use super::super::palette::{Palette, Rgb};
+use super::super::settings::Settings;
+
+// Terminals only report key presses, so a key is held down until no repeat
+// has arrived for this many milliseconds. This has to outlast the delay before
+// autorepeat starts, which is around half a second on most systems.
+const KEY_HOLD_TIME: u64 = 500;
+
+// Escape sequences sent by special keys and the names they are mapped by.
+const ESCAPE_SEQUENCES: [(&'static [u8], &'static str); 5] = [
+    (b"\x1b[24~", "f12"),
+    (b"\x1b[A", "up"),
+    (b"\x1b[B", "down"),
+    (b"\x1b[C", "right"),
+    (b"\x1b[D", "left"),
+];
+
+/// Returns the length of the CSI ("ESC [", parameters, final byte) or SS3
+/// ("ESC O" and a byte) sequence at the start of the input. A sequence cut
+/// off at the end of the input runs to its end.
+fn escape_sequence_len(input: &[u8]) -> Option<usize> {
+    match input.get(1) {
+        Some(&b'[') => {
+            // Parameter and intermediate bytes run up to a final byte from
+            // '@' to '~'.
+            let end = input[2..].iter().position(|&byte| byte >= 0x40 && byte <= 0x7E);
+            Some(end.map_or(input.len(), |end| end + 3))
+        },
+        Some(&b'O') => Some(input.len().min(3)),
+        _ => None,
+    }
+}
+
+/// A frontend that draws the display with unicode characters and ANSI colours
+/// and reads the keypad from stdin, for running roms over SSH.
+pub struct Terminal {
+    glyphs: Glyphs,
+    palette: Palette,
+    keymap: Keymap,
+
+    // Terminal attributes to restore when the frontend is dropped.
+    original: libc::termios,
+
+    // Time in milliseconds when each held keypad key is released.
+    release_at: [Option<u64>; 16],
+
+    // Whether the beep is currently playing, and whether the bell is kept
+    // quiet when it starts.
+    beeping: bool,
+    muted: bool,
+
+    // Resolution of the last frame drawn, so the screen can be cleared when a
+    // smaller display would leave parts of a larger one behind.
+    size: (usize, usize),
+
+    out: io::Stdout,
+}
+
+impl Terminal {
+    pub fn new(settings: &Settings, glyphs: Glyphs) -> io::Result<Terminal> {
+        // Switch stdin to raw mode so keys are read as soon as they are
+        // pressed, without echo and with reads that never block.
+        let original = unsafe {
+            let mut original: libc::termios = mem::zeroed();
+            if libc::tcgetattr(libc::STDIN_FILENO, &mut original) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            let mut raw = original;
+            raw.c_lflag &= !(libc::ICANON | libc::ECHO | libc::ISIG);
+            raw.c_iflag &= !(libc::IXON | libc::ICRNL);
+            raw.c_cc[libc::VMIN] = 0;
+            raw.c_cc[libc::VTIME] = 0;
+            if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            original
+        };
+
+        let mut terminal = Terminal {
+            glyphs: glyphs,
+            palette: settings.palette,
+            keymap: settings.keymap.clone(),
+            original: original,
+            release_at: [None; 16],
+            beeping: false,
+            muted: settings.tone.mute,
+            size: (0, 0),
+            out: io::stdout(),
+        };
+
+        // Use the alternate screen so the shell is left as it was on exit.
+        try!(terminal.write(b"\x1b[?1049h\x1b[?25l\x1b[2J"));
+        Ok(terminal)
+    }
+
+    /// Reads whatever is waiting on stdin without blocking.
+    fn read_input(&self) -> Vec<u8> {
+        let mut input = Vec::new();
+        let mut buffer = [0u8; 64];
+
+        loop {
+            let len = unsafe {
+                libc::read(libc::STDIN_FILENO, buffer.as_mut_ptr() as *mut libc::c_void,
+                           buffer.len() as libc::size_t)
+            };
+            if len <= 0 {
+                break;
+            }
+            input.extend_from_slice(&buffer[..len as usize]);
+        }
+
+        input
+    }
+
+    /// Renders a row of half blocks covering two lines of pixels.
+    fn render_half_blocks(&self, display: &Display, frame: &mut String) {
+        let width = display.width();
+        let pixels = display.pixels();
+
+        for row in 0..(display.height() + 1) / 2 {
+            let mut colors: Option<(Rgb, Rgb)> = None;
+
+            for x in 0..width {
+                let top = pixels[row * 2 * width + x];
+                let bottom = pixels.get((row * 2 + 1) * width + x).cloned().unwrap_or(0);
+                let cell = (self.palette.colors[top as usize], self.palette.colors[bottom as usize]);
+
+                if colors != Some(cell) {
+                    frame.push_str(&color_escape(38, cell.0));
+                    frame.push_str(&color_escape(48, cell.1));
+                    colors = Some(cell);
+                }
+                frame.push('\u{2580}');
+            }
+            frame.push_str("\x1b[0m\r\n");
+        }
+    }
+
+    /// Renders braille characters covering blocks of 2x4 pixels.
+    fn render_braille(&self, display: &Display, frame: &mut String) {
+        // Bits of the braille pattern for each dot, indexed by [y][x].
+        const DOTS: [[u32; 2]; 4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+
+        let width = display.width();
+        let height = display.height();
+        let pixels = display.pixels();
+
+        for row in 0..(height + 3) / 4 {
+            frame.push_str(&color_escape(38, self.palette.foreground()));
+            frame.push_str(&color_escape(48, self.palette.background()));
+
+            for column in 0..(width + 1) / 2 {
+                let mut pattern = 0;
+                for dy in 0..4 {
+                    for dx in 0..2 {
+                        let (x, y) = (column * 2 + dx, row * 4 + dy);
+                        if x < width && y < height && pixels[y * width + x] != 0 {
+                            pattern |= DOTS[dy][dx];
+                        }
+                    }
+                }
+                frame.push(::std::char::from_u32(0x2800 + pattern).unwrap_or(' '));
+            }
+            frame.push_str("\x1b[0m\r\n");
+        }
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> io::Result<()> {
+        let mut out = self.out.lock();
+        try!(out.write_all(bytes));
+        out.flush()
+    }
+}
+
+impl Frontend for Terminal {
+    fn present(&mut self, display: &Display, _: &[bool; 16]) {
+        if !display.dirty {
+            return;
+        }
+
+        // Draw the whole display from the top left corner in one write.
+        let mut frame = String::from("\x1b[H");
+        let size = (display.width(), display.height());
+        if size != self.size {
+            frame.push_str("\x1b[2J");
+            self.size = size;
+        }
+        match self.glyphs {
+            Glyphs::HalfBlock => self.render_half_blocks(display, &mut frame),
+            Glyphs::Braille => self.render_braille(display, &mut frame),
+        }
+        let _ = self.write(frame.as_bytes());
+    }
+
+    fn poll_events(&mut self) -> Vec<HostEvent> {
+        let current_time = time::precise_time_ns() / 1000000;
+        let input = self.read_input();
+        let mut events = Vec::new();
+        let mut i = 0;
+
+        while i < input.len() {
+            let sequence = ESCAPE_SEQUENCES.iter()
+                .find(|&&(sequence, _)| input[i..].starts_with(sequence));
+
+            let host_key = match (sequence, input[i]) {
+                (Some(&(sequence, name)), _) => {
+                    i += sequence.len();
+                    name.to_string()
+                },
+
+                // Other special keys are skipped whole so their bytes are not
+                // taken for keys of the keypad.
+                (None, 0x1b) if escape_sequence_len(&input[i..]).is_some() => {
+                    i += escape_sequence_len(&input[i..]).unwrap();
+                    continue;
+                },
+
+                // Ctrl-C or a lone escape quits.
+                (None, 0x03) => { i += 1; events.push(HostEvent::Quit); continue; },
+                (None, 0x1b) if input.len() == 1 => { i += 1; events.push(HostEvent::Quit); continue; },
+                (None, b' ') => { i += 1; "space".to_string() },
+                (None, byte) => { i += 1; (byte as char).to_string() },
+            };
+
+            if host_key == "f12" {
+                events.push(HostEvent::Screenshot);
+            } else if let Some(key) = self.keymap.get(&host_key) {
+                if self.release_at[key as usize].is_none() {
+                    events.push(HostEvent::Key(key, true));
+                }
+                self.release_at[key as usize] = Some(current_time + KEY_HOLD_TIME);
+            }
+        }
+
+        // Release keys that have not been repeated recently.
+        for key in 0..16 {
+            if let Some(release_at) = self.release_at[key] {
+                if current_time >= release_at {
+                    self.release_at[key] = None;
+                    events.push(HostEvent::Key(key as u8, false));
+                }
+            }
+        }
+
+        events
+    }
+
+    /// Rings the terminal bell when a beep starts.
+    fn set_beeping(&mut self, _: u64, beeping: bool) {
+        if beeping && !self.beeping && !self.muted {
+            let _ = self.write(b"\x07");
+        }
+        self.beeping = beeping;
+    }
+
+    fn set_muted(&mut self, muted: bool) {
+        self.muted = muted;
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        let _ = self.write(b"\x1b[0m\x1b[?25h\x1b[?1049l");
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.original);
+        }
+    }
+}
+
+/// Returns the escape sequence setting a 24-bit foreground (38) or background
+/// (48) colour.
+fn color_escape(layer: u8, rgb: Rgb) -> String {
+    format!("\x1b[{};2;{};{};{}m", layer, rgb.0, rgb.1, rgb.2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::escape_sequence_len;
+
+    #[test]
+    fn csi_sequences_run_to_their_final_byte() {
+        assert_eq!(escape_sequence_len(b"\x1b[15~5"), Some(5));
+        assert_eq!(escape_sequence_len(b"\x1b[H"), Some(3));
+        assert_eq!(escape_sequence_len(b"\x1b[1;5C"), Some(6));
+    }
+
+    #[test]
+    fn ss3_sequences_are_three_bytes() {
+        assert_eq!(escape_sequence_len(b"\x1bOP1"), Some(3));
+    }
+
+    #[test]
+    fn truncated_sequences_take_the_rest_of_the_input() {
+        assert_eq!(escape_sequence_len(b"\x1b[15"), Some(4));
+        assert_eq!(escape_sequence_len(b"\x1bO"), Some(2));
+    }
+
+    #[test]
+    fn other_escapes_are_not_sequences() {
+        assert_eq!(escape_sequence_len(b"\x1b"), None);
+        assert_eq!(escape_sequence_len(b"\x1b5"), None);
+    }
+}
@@ -0,0 +1,494 @@
+use std::io::{self, Write};
+
+use super::palette::{Palette, Rgb, PALETTE_SIZE};
+
+// Largest code a GIF LZW stream may use.
+const MAX_CODE: u16 = 4095;
+
+// Data in a GIF is split into blocks of at most this many bytes.
+const MAX_SUB_BLOCK: usize = 255;
+
+// Block introducers and the trailer that ends the file.
+const EXTENSION: u8 = 0x21;
+const IMAGE: u8 = 0x2C;
+const TRAILER: u8 = 0x3B;
+
+/// Writes an animated GIF one frame at a time. Frames are made of palette
+/// indices, so the display maps onto a GIF without any colour quantisation.
+pub struct GifEncoder<W: Write> {
+    out: W,
+    width: usize,
+    height: usize,
+}
+
+impl<W: Write> GifEncoder<W> {
+    /// Writes the header, palette and looping extension of an animation.
+    pub fn new(mut out: W, width: usize, height: usize, palette: &Palette) -> io::Result<GifEncoder<W>> {
+        try!(out.write_all(b"GIF89a"));
+
+        // Logical screen descriptor with a global colour table of 4 colours.
+        try!(write_u16(&mut out, width as u16));
+        try!(write_u16(&mut out, height as u16));
+        try!(out.write_all(&[0xF1, 0, 0]));
+        for &Rgb(r, g, b) in palette.colors.iter() {
+            try!(out.write_all(&[r, g, b]));
+        }
+
+        // Netscape application extension to loop the animation forever.
+        try!(out.write_all(&[EXTENSION, 0xFF, 0x0B]));
+        try!(out.write_all(b"NETSCAPE2.0"));
+        try!(out.write_all(&[0x03, 0x01, 0x00, 0x00, 0x00]));
+
+        Ok(GifEncoder {
+            out: out,
+            width: width,
+            height: height,
+        })
+    }
+
+    /// Adds a frame of palette indices shown for `delay` hundredths of a
+    /// second.
+    pub fn write_frame(&mut self, indices: &[u8], delay: u16) -> io::Result<()> {
+        // Graphic control extension holding the frame delay.
+        try!(self.out.write_all(&[EXTENSION, 0xF9, 0x04, 0x00]));
+        try!(write_u16(&mut self.out, delay));
+        try!(self.out.write_all(&[0x00, 0x00]));
+
+        // Image descriptor covering the whole screen.
+        try!(self.out.write_all(&[IMAGE, 0, 0, 0, 0]));
+        try!(write_u16(&mut self.out, self.width as u16));
+        try!(write_u16(&mut self.out, self.height as u16));
+        try!(self.out.write_all(&[0x00]));
+
+        let min_code_size = min_code_size();
+        let data = lzw_encode(indices, min_code_size);
+        try!(self.out.write_all(&[min_code_size]));
+        for block in data.chunks(MAX_SUB_BLOCK) {
+            try!(self.out.write_all(&[block.len() as u8]));
+            try!(self.out.write_all(block));
+        }
+        self.out.write_all(&[0x00])
+    }
+
+    /// Writes the trailer, completing the file.
+    pub fn finish(&mut self) -> io::Result<()> {
+        try!(self.out.write_all(&[TRAILER]));
+        self.out.flush()
+    }
+}
+
+/// Returns true if the data starts with a GIF signature.
+pub fn is_gif(data: &[u8]) -> bool {
+    data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a")
+}
+
+/// Decodes the palette indices of every image in a GIF, in the order they
+/// appear. Images are returned as they are stored, without compositing them
+/// onto the logical screen.
+pub fn decode_frames(data: &[u8]) -> Result<Vec<Vec<u8>>, String> {
+    if !is_gif(data) {
+        return Err("not a GIF image".to_string());
+    }
+    let mut reader = ByteReader { data: data, pos: 6 };
+
+    // Skip the logical screen descriptor and the global colour table.
+    try!(reader.skip(4));
+    let flags = try!(reader.byte());
+    try!(reader.skip(2));
+    if flags & 0x80 != 0 {
+        try!(reader.skip(3 << ((flags & 0x07) + 1)));
+    }
+
+    let mut frames = Vec::new();
+    loop {
+        match try!(reader.byte()) {
+            EXTENSION => {
+                try!(reader.byte());
+                try!(reader.sub_blocks());
+            },
+            IMAGE => {
+                try!(reader.skip(4));
+                let width = try!(reader.u16()) as usize;
+                let height = try!(reader.u16()) as usize;
+                let flags = try!(reader.byte());
+                if flags & 0x80 != 0 {
+                    try!(reader.skip(3 << ((flags & 0x07) + 1)));
+                }
+
+                let min_code_size = try!(reader.byte());
+                if min_code_size < 2 || min_code_size > 8 {
+                    return Err(format!("invalid LZW code size {}", min_code_size));
+                }
+                let data = try!(reader.sub_blocks());
+                let mut indices = try!(lzw_decode(&data, min_code_size));
+                indices.resize(width * height, 0);
+
+                if flags & 0x40 != 0 {
+                    indices = deinterlace(&indices, width, height);
+                }
+                frames.push(indices);
+            },
+            TRAILER => break,
+            block => return Err(format!("unknown block {:#04x}", block)),
+        }
+    }
+
+    Ok(frames)
+}
+
+/// The smallest LZW code size that fits every palette index. GIF does not
+/// allow code sizes below 2.
+fn min_code_size() -> u8 {
+    let mut size = 2;
+    while (1 << size) < PALETTE_SIZE {
+        size += 1;
+    }
+    size
+}
+
+fn write_u16<W: Write>(out: &mut W, value: u16) -> io::Result<()> {
+    out.write_all(&[value as u8, (value >> 8) as u8])
+}
+
+/// Packs variable length codes into bytes least significant bit first.
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u32,
+    bits: u8,
+}
+
+impl BitWriter {
+    fn write(&mut self, code: u16, size: u8) {
+        self.buffer |= (code as u32) << self.bits;
+        self.bits += size;
+        while self.bits >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.bits -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bits > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        self.bytes
+    }
+}
+
+/// Compresses palette indices with the variable length LZW used by GIF.
+fn lzw_encode(indices: &[u8], min_code_size: u8) -> Vec<u8> {
+    let clear_code: u16 = 1 << min_code_size;
+    let end_code = clear_code + 1;
+
+    let mut writer = BitWriter { bytes: Vec::new(), buffer: 0, bits: 0 };
+    let mut table: Vec<[u16; PALETTE_SIZE]> = Vec::new();
+    let mut code_size = min_code_size + 1;
+    let mut next_code = end_code + 1;
+
+    // The table maps (prefix code, index) to the code of the longer string.
+    // Zero marks an empty entry since code zero can never be a suffix entry.
+    let reset = |table: &mut Vec<[u16; PALETTE_SIZE]>| {
+        table.clear();
+        for _ in 0..(MAX_CODE as usize + 1) {
+            table.push([0; PALETTE_SIZE]);
+        }
+    };
+    reset(&mut table);
+    writer.write(clear_code, code_size);
+
+    let mut prefix: Option<u16> = None;
+    for &index in indices {
+        let current = match prefix {
+            None => {
+                prefix = Some(index as u16);
+                continue;
+            },
+            Some(current) => current,
+        };
+
+        let entry = table[current as usize][index as usize];
+        if entry != 0 {
+            prefix = Some(entry);
+            continue;
+        }
+
+        writer.write(current, code_size);
+
+        if next_code <= MAX_CODE {
+            table[current as usize][index as usize] = next_code;
+            if next_code == (1 << code_size) && code_size < 12 {
+                code_size += 1;
+            }
+            next_code += 1;
+        } else {
+            // The table is full, start over.
+            writer.write(clear_code, code_size);
+            reset(&mut table);
+            code_size = min_code_size + 1;
+            next_code = end_code + 1;
+        }
+
+        prefix = Some(index as u16);
+    }
+
+    if let Some(current) = prefix {
+        writer.write(current, code_size);
+    }
+    writer.write(end_code, code_size);
+    writer.finish()
+}
+
+/// Reads the fields of a GIF, failing when the file ends early.
+struct ByteReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    fn byte(&mut self) -> Result<u8, String> {
+        match self.data.get(self.pos) {
+            Some(&byte) => {
+                self.pos += 1;
+                Ok(byte)
+            },
+            None => Err("unexpected end of GIF".to_string()),
+        }
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        let low = try!(self.byte()) as u16;
+        let high = try!(self.byte()) as u16;
+        Ok(high << 8 | low)
+    }
+
+    fn skip(&mut self, count: usize) -> Result<(), String> {
+        if self.pos + count > self.data.len() {
+            return Err("unexpected end of GIF".to_string());
+        }
+        self.pos += count;
+        Ok(())
+    }
+
+    /// Joins a sequence of sub-blocks ending with an empty one.
+    fn sub_blocks(&mut self) -> Result<Vec<u8>, String> {
+        let mut data = Vec::new();
+        loop {
+            let size = try!(self.byte()) as usize;
+            if size == 0 {
+                return Ok(data);
+            }
+            let start = self.pos;
+            try!(self.skip(size));
+            data.extend_from_slice(&self.data[start..start + size]);
+        }
+    }
+}
+
+/// Unpacks variable length codes from bytes least significant bit first.
+struct BitReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    buffer: u32,
+    bits: u8,
+}
+
+impl<'a> BitReader<'a> {
+    fn read(&mut self, size: u8) -> Option<u16> {
+        while self.bits < size {
+            match self.bytes.get(self.pos) {
+                Some(&byte) => self.buffer |= (byte as u32) << self.bits,
+                None => return None,
+            }
+            self.pos += 1;
+            self.bits += 8;
+        }
+
+        let code = (self.buffer & ((1 << size) - 1)) as u16;
+        self.buffer >>= size;
+        self.bits -= size;
+        Some(code)
+    }
+}
+
+/// Decompresses palette indices from the variable length LZW used by GIF.
+/// Streams that end without an end code are accepted, as most decoders do.
+fn lzw_decode(data: &[u8], min_code_size: u8) -> Result<Vec<u8>, String> {
+    let clear_code: u16 = 1 << min_code_size;
+    let end_code = clear_code + 1;
+
+    let mut reader = BitReader { bytes: data, pos: 0, buffer: 0, bits: 0 };
+    let mut indices = Vec::new();
+
+    // Each entry is a string of indices, stored as the code of its prefix
+    // and its last index. Entries up to the end code are single indices.
+    let mut table: Vec<(Option<u16>, u8)> = Vec::new();
+    let mut code_size = min_code_size + 1;
+    let mut previous: Option<u16> = None;
+
+    // Writes the string of a code and returns its first index.
+    let expand = |table: &Vec<(Option<u16>, u8)>, code: u16, out: &mut Vec<u8>| -> u8 {
+        let start = out.len();
+        let mut current = Some(code);
+        while let Some(code) = current {
+            let (prefix, index) = table[code as usize];
+            out.push(index);
+            current = prefix;
+        }
+        out[start..].reverse();
+        out[start]
+    };
+
+    let reset = |table: &mut Vec<(Option<u16>, u8)>| {
+        table.clear();
+        for index in 0..(end_code + 1) {
+            table.push((None, index as u8));
+        }
+    };
+    reset(&mut table);
+
+    while let Some(code) = reader.read(code_size) {
+        if code == clear_code {
+            reset(&mut table);
+            code_size = min_code_size + 1;
+            previous = None;
+            continue;
+        }
+        if code == end_code {
+            break;
+        }
+
+        let prefix = match previous {
+            None => {
+                if code >= clear_code {
+                    return Err(format!("invalid LZW code {}", code));
+                }
+                indices.push(code as u8);
+                previous = Some(code);
+                continue;
+            },
+            Some(prefix) => prefix,
+        };
+
+        // A code one past the end of the table is the previous string
+        // followed by its own first index.
+        let first = if (code as usize) < table.len() {
+            expand(&table, code, &mut indices)
+        } else if code as usize == table.len() {
+            let first = expand(&table, prefix, &mut indices);
+            indices.push(first);
+            first
+        } else {
+            return Err(format!("invalid LZW code {}", code));
+        };
+
+        if table.len() <= MAX_CODE as usize {
+            table.push((Some(prefix), first));
+            if table.len() == (1 << code_size) && code_size < 12 {
+                code_size += 1;
+            }
+        }
+        previous = Some(code);
+    }
+
+    Ok(indices)
+}
+
+/// Reorders the rows of an interlaced image, which are stored in four passes
+/// starting at rows 0, 4, 2 and 1.
+fn deinterlace(indices: &[u8], width: usize, height: usize) -> Vec<u8> {
+    let mut rows = Vec::new();
+    for &(start, step) in [(0, 8), (4, 8), (2, 4), (1, 2)].iter() {
+        let mut row = start;
+        while row < height {
+            rows.push(row);
+            row += step;
+        }
+    }
+
+    let mut output = vec![0; width * height];
+    for (stored, &row) in rows.iter().enumerate() {
+        output[row * width..(row + 1) * width]
+            .copy_from_slice(&indices[stored * width..(stored + 1) * width]);
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_frames, deinterlace, lzw_decode, lzw_encode, min_code_size, GifEncoder};
+    use super::super::palette::{Palette, PALETTE_SIZE};
+
+    /// Indices with some repetition, like a display, but not so much that
+    /// the strings stay short.
+    fn pattern(length: usize) -> Vec<u8> {
+        let mut state: u32 = 1;
+        (0..length).map(|i| {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            if i % 7 < 3 { 0 } else { (state >> 16) as u8 % PALETTE_SIZE as u8 }
+        }).collect()
+    }
+
+    fn round_trip(indices: &[u8]) -> Vec<u8> {
+        let data = lzw_encode(indices, min_code_size());
+        lzw_decode(&data, min_code_size()).unwrap()
+    }
+
+    #[test]
+    fn lzw_round_trips_short_streams() {
+        assert_eq!(round_trip(&[]), Vec::<u8>::new());
+        assert_eq!(round_trip(&[3]), vec![3]);
+        assert_eq!(round_trip(&[1, 1, 1, 1, 1, 1, 1]), vec![1, 1, 1, 1, 1, 1, 1]);
+        assert_eq!(round_trip(&[0, 1, 2, 3, 0, 1, 2, 3, 0, 1]), vec![0, 1, 2, 3, 0, 1, 2, 3, 0, 1]);
+    }
+
+    #[test]
+    fn lzw_round_trips_across_code_sizes_and_table_resets() {
+        // Enough varied data to grow the codes to 12 bits and fill the table
+        // several times over.
+        let indices = pattern(200000);
+        assert_eq!(round_trip(&indices), indices);
+
+        let blank = vec![0; 128 * 64 * 16];
+        assert_eq!(round_trip(&blank), blank);
+    }
+
+    #[test]
+    fn lzw_rejects_codes_past_the_table() {
+        // Clear, then a code that has not been defined yet.
+        let data = [0x04 | 0x0F << 3, 0x00];
+        assert!(lzw_decode(&data, 2).is_err());
+    }
+
+    #[test]
+    fn deinterlace_puts_every_pass_back_in_place() {
+        let (width, height) = (3, 11);
+        let image: Vec<u8> = (0..width * height).map(|i| i as u8).collect();
+
+        // Store the rows in the order of the four interlaced passes.
+        let mut interlaced = Vec::new();
+        for &(start, step) in [(0, 8), (4, 8), (2, 4), (1, 2)].iter() {
+            for row in (start..height).filter(|row| (row - start) % step == 0) {
+                interlaced.extend_from_slice(&image[row * width..(row + 1) * width]);
+            }
+        }
+
+        assert_eq!(deinterlace(&interlaced, width, height), image);
+    }
+
+    #[test]
+    fn decodes_the_frames_it_encodes() {
+        let first = pattern(64 * 32);
+        let second = vec![2; 64 * 32];
+
+        let mut gif = Vec::new();
+        {
+            let mut encoder = GifEncoder::new(&mut gif, 64, 32, &Palette::default()).unwrap();
+            encoder.write_frame(&first, 5).unwrap();
+            encoder.write_frame(&second, 5).unwrap();
+            encoder.finish().unwrap();
+        }
+
+        assert_eq!(decode_frames(&gif).unwrap(), vec![first, second]);
+    }
+}
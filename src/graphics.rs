@@ -1,133 +1,327 @@
-use super::sdl2::pixels::Color;
-use super::sdl2::rect::Point;
+use super::sdl2::pixels::{Color, PixelFormatEnum};
+use super::sdl2::rect::Rect;
+use super::sdl2::render::Texture;
+use super::sdl2::video::FullscreenType;
 use super::sdl2;
 
-// Display size parameters.
-const DISPLAY_WIDTH: usize = 64;
-const DISPLAY_HEIGHT: usize = 32;
-const DISPLAY_SIZE: usize = DISPLAY_WIDTH * DISPLAY_HEIGHT;
+use super::display::{Display, DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use super::filter::DisplayFilter;
+use super::palette::{Palette, Rgb};
+use super::settings::Settings;
+
+// Bytes per pixel in the RGB24 streaming texture.
+const BYTES_PER_PIXEL: usize = 3;
+
+// Keys of the on-screen keypad by row, laid out like the COSMAC VIP's.
+const KEYPAD_LAYOUT: [[u8; 4]; 4] = [
+    [0x1, 0x2, 0x3, 0xC],
+    [0x4, 0x5, 0x6, 0xD],
+    [0x7, 0x8, 0x9, 0xE],
+    [0xA, 0x0, 0xB, 0xF],
+];
+
+// Hex digits the keypad and the memory viewer are labelled with, four pixels
+// wide and five tall with each row in the high bits of a byte. They are kept
+// apart from the font programs draw with so labels stay legible whichever
+// font a rom uses.
+pub const LABEL_GLYPHS: [[u8; 5]; 16] = [
+    [0xF0, 0x90, 0x90, 0x90, 0xF0], // 0
+    [0x20, 0x60, 0x20, 0x20, 0x70], // 1
+    [0xF0, 0x10, 0xF0, 0x80, 0xF0], // 2
+    [0xF0, 0x10, 0xF0, 0x10, 0xF0], // 3
+    [0x90, 0x90, 0xF0, 0x10, 0x10], // 4
+    [0xF0, 0x80, 0xF0, 0x10, 0xF0], // 5
+    [0xF0, 0x80, 0xF0, 0x90, 0xF0], // 6
+    [0xF0, 0x10, 0x20, 0x40, 0x40], // 7
+    [0xF0, 0x90, 0xF0, 0x90, 0xF0], // 8
+    [0xF0, 0x90, 0xF0, 0x10, 0xF0], // 9
+    [0xF0, 0x90, 0xF0, 0x90, 0x90], // A
+    [0xE0, 0x90, 0xE0, 0x90, 0xE0], // B
+    [0xF0, 0x80, 0x80, 0x80, 0xF0], // C
+    [0xE0, 0x90, 0x90, 0x90, 0xE0], // D
+    [0xF0, 0x80, 0xF0, 0x80, 0xF0], // E
+    [0xF0, 0x80, 0xF0, 0x80, 0x80], // F
+];
 
 pub struct Graphics {
     renderer: sdl2::render::Renderer<'static>,
 
-    // 64x32 buffer for the application to write to. The contents of this buffer
-    // is rendered to the SDL surface.
-    display: Vec<u8>,
+    // Streaming texture the display buffer is uploaded into once per frame.
+    // The renderer scales it up to the size of the window when copying.
+    texture: Texture,
+
+    // Resolution of the texture, which follows the resolution of the display.
+    width: usize,
+    height: usize,
+
+    // Colours used for each pixel value on the display.
+    palette: Palette,
+
+    // Reduces flicker by blending the display over multiple frames.
+    filter: DisplayFilter,
+
+    // Only scale the display by whole numbers so every pixel is the same size.
+    integer_scaling: bool,
+
+    // True while the window covers the whole screen.
+    fullscreen: bool,
+
+    // Show a clickable keypad to the right of the display.
+    keypad: bool,
 }
 
 impl Graphics {
-    pub fn new(sdl_context: &sdl2::Sdl) -> Graphics {
-        let video_subsystem = sdl_context.video().unwrap();
-
-        // Create a window 10x the scale of CHIP-8's display.
-        let window = video_subsystem.window("Notch", 640, 320)
-            .position_centered()
-            .build()
-            .unwrap();
-
-        // Create a renderer that is scaled up a bit. The CHIP-8 display is
-        // very small for today's standards.
-        let mut renderer = window.renderer().build().unwrap();
-        renderer.set_scale(10.0, 10.0);
-
-        // Clear the screen to black.
-        renderer.set_draw_color(Color::RGB(0, 0, 0));
+    pub fn new(sdl_context: &sdl2::Sdl, settings: &Settings) -> sdl2::SdlResult<Graphics> {
+        let video_subsystem = try!(sdl_context.video());
+
+        // Create a resizable window scaled up from CHIP-8's display. The
+        // CHIP-8 display is very small for today's standards.
+        // The keypad is a square as tall as the display.
+        let width = if settings.keypad {
+            DISPLAY_WIDTH + DISPLAY_HEIGHT
+        } else {
+            DISPLAY_WIDTH
+        };
+        // Known roms show their title.
+        let title = match settings.title {
+            Some(ref title) => format!("Notch - {}", title),
+            None => "Notch".to_string(),
+        };
+        let mut builder = video_subsystem.window(&title,
+                                                 width as u32 * settings.scale,
+                                                 DISPLAY_HEIGHT as u32 * settings.scale);
+        builder.position_centered().resizable();
+        if settings.fullscreen {
+            builder.fullscreen_desktop();
+        }
+        let window = try!(builder.build());
+
+        // The display is drawn into a texture the size of the CHIP-8 display
+        // which is scaled up to fit the window.
+        let mut renderer = try!(window.renderer().build());
+        let texture = try!(Graphics::create_texture(&renderer, DISPLAY_WIDTH, DISPLAY_HEIGHT));
+        let palette = settings.palette;
+
+        // Clear the screen to the background colour.
+        renderer.set_draw_color(to_color(palette.background()));
         renderer.clear();
         renderer.present();
 
-        Graphics {
+        Ok(Graphics {
             renderer: renderer,
-            display: vec![0; DISPLAY_SIZE],
+            texture: texture,
+            width: DISPLAY_WIDTH,
+            height: DISPLAY_HEIGHT,
+            palette: palette,
+            filter: DisplayFilter::new(settings.filter, DISPLAY_WIDTH * DISPLAY_HEIGHT),
+            integer_scaling: settings.integer_scaling,
+            fullscreen: settings.fullscreen,
+            keypad: settings.keypad,
+        })
+    }
+
+    /// Switches between windowed and fullscreen mode.
+    pub fn toggle_fullscreen(&mut self) {
+        let fullscreen_type = if self.fullscreen {
+            FullscreenType::Off
+        } else {
+            FullscreenType::Desktop
+        };
+
+        if let Some(window) = self.renderer.window_mut() {
+            match window.set_fullscreen(fullscreen_type) {
+                Ok(_) => self.fullscreen = !self.fullscreen,
+                Err(why) => println!("notch: cannot toggle fullscreen: {}", why),
+            }
         }
     }
 
-    /// Draws a sprite to the display.
-    pub fn draw(&mut self, x: usize, y: usize, sprite: Vec<u8>) -> u8 {
-        let line = y * DISPLAY_WIDTH;
-        let mut collision: u8 = 0;
-        let mut values = vec![0 as u8; 8];
+    /// Shows or hides the on-screen keypad.
+    pub fn toggle_keypad(&mut self) {
+        self.keypad = !self.keypad;
+    }
 
-        for i in 0..sprite.len() {
-            // Each byte in a sprite draws on one line.
-            let offset = line + DISPLAY_WIDTH * i;
+    /// Draw the display in it's current state to the SDL surface. This is
+    /// called once per frame rather than on every draw instruction. Humans
+    /// have these things called "eyes" and they get upset when they cannot see
+    /// things. The frame is skipped if the display cannot be uploaded.
+    pub fn present(&mut self, display: &Display, keys: &[bool; 16]) -> sdl2::SdlResult<()> {
+        // Recreate the texture when the resolution changes, such as when
+        // switching to SCHIP high resolution mode.
+        let resized = display.width() != self.width || display.height() != self.height;
+        if resized {
+            self.texture = try!(Graphics::create_texture(&self.renderer, display.width(),
+                                                         display.height()));
+            self.width = display.width();
+            self.height = display.height();
+        }
 
-            // Organize the bits from the current sprite byte into a slice.
-            for j in 0..values.len() {
-                let bit = (sprite[i] >> j) & 0x01;
-                values[8 - 1 - j] = bit;
-            }
+        // Filters fade pixels out over time so the output changes every frame.
+        if resized || display.dirty || self.filter.is_active() {
+            try!(self.upload_display(display));
+        }
 
-            // Loop through the bits in the current byte and set the display
-            // values based on them.
-            for j in 0..values.len() {
-                let value = values[j];
-                let pos: usize = x + j;
-                let mut index: usize;
-
-                // Draw a pixel in the sprite onto the display. If the pixel x
-                // position is greater than the width of the display, the sprite
-                // wraps around the display.
-                if pos >= DISPLAY_WIDTH {
-                    // Wrap around to the left side to draw.
-                    index = offset + pos - DISPLAY_WIDTH;
-                } else {
-                    // Draw at the current offset.
-                    index = offset + pos;
-                }
+        // Clear the window to black for letterboxing and scale the texture
+        // up to fit inside of it.
+        let (viewport, keypad) = self.layout();
+        self.renderer.set_draw_color(Color::RGB(0, 0, 0));
+        self.renderer.clear();
+        self.renderer.copy(&self.texture, None, viewport);
+        if let Some(keypad) = keypad {
+            self.draw_keypad(keypad, keys);
+        }
+        self.renderer.present();
+        Ok(())
+    }
 
-                if index >= DISPLAY_SIZE {
-                    index -= DISPLAY_SIZE;
-                }
+    /// Returns the key of the on-screen keypad at a point in the window.
+    pub fn keypad_key_at(&self, x: i32, y: i32) -> Option<u8> {
+        let (keypad_x, keypad_y, size) = match self.layout().1 {
+            Some(keypad) => keypad,
+            None => return None,
+        };
 
-                if index < DISPLAY_SIZE {
-                    // Save the previous state of the pixel before setting it
-                    // for collision detection.
-                    let prev = self.display[index];
+        let size = size as i32;
+        let (column, row) = ((x - keypad_x) * 4, (y - keypad_y) * 4);
+        if column < 0 || row < 0 || column >= size * 4 || row >= size * 4 {
+            return None;
+        }
 
-                    // Draw the bit to the display.
-                    self.display[index] = value ^ prev;
+        Some(KEYPAD_LAYOUT[(row / size) as usize][(column / size) as usize])
+    }
+
+    /// Size of the window's drawable area in pixels.
+    pub fn output_size(&self) -> (u32, u32) {
+        self.renderer.output_size().unwrap_or((0, 0))
+    }
+
+    /// Draws the keypad as a grid of keys labelled with hex digits.
+    /// Pressed keys are drawn in the foreground colour.
+    fn draw_keypad(&mut self, keypad: (i32, i32, u32), keys: &[bool; 16]) {
+        let (keypad_x, keypad_y, size) = keypad;
+        let cell = size as i32 / 4;
+        let margin = cell / 10;
+        let pixel = (cell / 10).max(1);
+
+        let background = self.palette.background();
+        let foreground = self.palette.foreground();
+
+        for (row, keys_in_row) in KEYPAD_LAYOUT.iter().enumerate() {
+            for (column, &key) in keys_in_row.iter().enumerate() {
+                let x = keypad_x + column as i32 * cell;
+                let y = keypad_y + row as i32 * cell;
+                let pressed = keys[key as usize];
+
+                let (face, label) = if pressed {
+                    (foreground, background)
+                } else {
+                    (background.blend(foreground, 0.25), foreground)
+                };
 
-                    // Check the previous state of the pixel and check if it
-                    // was erased, if so then there was a sprite collision.
-                    if prev == 1 && self.display[index] == 0 {
-                        collision = 1;
+                self.renderer.set_draw_color(to_color(face));
+                fill_rect(&mut self.renderer, x + margin, y + margin,
+                          cell - margin * 2, cell - margin * 2);
+
+                // Center the 4x5 character on the key.
+                self.renderer.set_draw_color(to_color(label));
+                let label_x = x + (cell - pixel * 4) / 2;
+                let label_y = y + (cell - pixel * 5) / 2;
+                for (line, &bits) in LABEL_GLYPHS[key as usize].iter().enumerate() {
+                    for bit in 0..4 {
+                        if bits & (0x80 >> bit) != 0 {
+                            fill_rect(&mut self.renderer, label_x + bit * pixel,
+                                      label_y + line as i32 * pixel, pixel, pixel);
+                        }
                     }
                 }
             }
         }
+    }
 
-        // Draw to the SDL surface. Humans have these things called "eyes" and
-        // they get upset when they cannot see things.
-        self.draw_display();
+    /// Calculates where the display and keypad are drawn in the window. They
+    /// are scaled as large as possible while keeping their aspect ratio and
+    /// centered with bars filling the remaining space. The keypad is given as
+    /// its top left corner and size since it is square.
+    fn layout(&self) -> (Option<Rect>, Option<(i32, i32, u32)>) {
+        let (output_width, output_height) = match self.renderer.output_size() {
+            Ok(size) => size,
+            Err(_) => return (None, None),
+        };
 
-        collision
-    }
+        // The keypad is a square as tall as the display.
+        let keypad_width = if self.keypad { self.height } else { 0 };
+
+        let scale_x = output_width as f32 / (self.width + keypad_width) as f32;
+        let scale_y = output_height as f32 / self.height as f32;
+        let mut scale = scale_x.min(scale_y);
 
-    /// Clears all pixels on the display by setting them all to an off state.
-    pub fn clear_display(&mut self) {
-        for i in 0..DISPLAY_SIZE {
-            self.display[i] = 0;
+        // Whole number scaling is only possible if the window is at least as
+        // large as the display.
+        if self.integer_scaling && scale >= 1.0 {
+            scale = scale.floor();
         }
-        self.draw_display();
+
+        let width = (self.width as f32 * scale) as u32;
+        let height = (self.height as f32 * scale) as u32;
+        let x = output_width.saturating_sub(width + (keypad_width as f32 * scale) as u32) / 2;
+        let y = output_height.saturating_sub(height) / 2;
+
+        let viewport = Rect::new(x as i32, y as i32, width, height).unwrap_or(None);
+        let keypad = if self.keypad {
+            Some(((x + width) as i32, y as i32, height))
+        } else {
+            None
+        };
+
+        (viewport, keypad)
     }
 
-    /// Draw the display in it's current state to the SDL surface.
-    /// All pixels are white but this may be subject to change.
-    fn draw_display(&mut self) {
-        // Clear the screen to black.
-        self.renderer.set_draw_color(Color::RGB(0, 0, 0));
-        self.renderer.clear();
+    /// Creates a streaming texture to upload a display of the given size to.
+    fn create_texture(renderer: &sdl2::render::Renderer, width: usize, height: usize)
+                      -> sdl2::SdlResult<Texture> {
+        renderer.create_texture_streaming(PixelFormatEnum::RGB24, (width as u32, height as u32))
+    }
+
+    /// Copies the display into the streaming texture, translating each pixel
+    /// value into its colour in the palette. Pixels are blended with the
+    /// background by their brightness after filtering.
+    fn upload_display(&mut self, display: &Display) -> sdl2::SdlResult<()> {
+        self.filter.apply(display.pixels());
 
-        // Draw the display to the SDL surface.
-        self.renderer.set_draw_color(Color::RGB(255, 255, 255));
-        for i in 0..DISPLAY_HEIGHT {
-            let offset = DISPLAY_WIDTH * i;
-            for j in 0..DISPLAY_WIDTH {
-                if self.display[offset + j] == 1 {
-                    self.renderer.draw_point(Point::new(j as i32, i as i32));
+        let filter = &self.filter;
+        let palette = &self.palette;
+        let width = self.width;
+        let height = self.height;
+
+        self.texture.with_lock(None, |buffer: &mut [u8], pitch: usize| {
+            for i in 0..height {
+                let offset = width * i;
+                for j in 0..width {
+                    let (value, level) = filter.pixel(offset + j);
+                    let color = palette.colors[value as usize];
+                    let Rgb(r, g, b) = palette.background().blend(color, level);
+                    let pos = pitch * i + BYTES_PER_PIXEL * j;
+
+                    buffer[pos] = r;
+                    buffer[pos + 1] = g;
+                    buffer[pos + 2] = b;
                 }
             }
-        }
-        self.renderer.present();
+        })
+    }
+}
+
+/// Converts a palette colour into an SDL colour.
+fn to_color(rgb: Rgb) -> Color {
+    Color::RGB(rgb.0, rgb.1, rgb.2)
+}
+
+/// Fills a rectangle with the current draw colour, skipping empty ones.
+fn fill_rect(renderer: &mut sdl2::render::Renderer, x: i32, y: i32, width: i32, height: i32) {
+    if width <= 0 || height <= 0 {
+        return;
+    }
+    if let Ok(Some(rect)) = Rect::new(x, y, width as u32, height as u32) {
+        renderer.fill_rect(rect);
     }
 }
@@ -0,0 +1,302 @@
+// Longest Huffman code deflate allows.
+const MAX_BITS: usize = 15;
+
+// Base lengths and extra bits of the length symbols 257 to 285.
+const LENGTH_BASE: [u16; 29] = [3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35,
+                                43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258];
+const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4,
+                                4, 4, 4, 5, 5, 5, 5, 0];
+
+// Base distances and extra bits of the distance symbols 0 to 29.
+const DIST_BASE: [u16; 30] = [1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257,
+                              385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289,
+                              16385, 24577];
+const DIST_EXTRA: [u8; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9,
+                              9, 10, 10, 11, 11, 12, 12, 13, 13];
+
+// Order the code lengths of the code length alphabet are stored in.
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2,
+                                        14, 1, 15];
+
+/// Decompresses a raw deflate stream, as stored in zip archives.
+pub fn inflate(data: &[u8]) -> Result<Vec<u8>, String> {
+    let mut reader = BitReader { bytes: data, pos: 0, buffer: 0, bits: 0 };
+    let mut out = Vec::new();
+
+    loop {
+        let last = try!(reader.read(1)) == 1;
+        match try!(reader.read(2)) {
+            0 => try!(inflate_stored(&mut reader, &mut out)),
+            1 => {
+                let (lengths, distances) = fixed_codes();
+                try!(inflate_block(&mut reader, &mut out, &lengths, &distances));
+            },
+            2 => {
+                let (lengths, distances) = try!(dynamic_codes(&mut reader));
+                try!(inflate_block(&mut reader, &mut out, &lengths, &distances));
+            },
+            _ => return Err("invalid deflate block type".to_string()),
+        }
+        if last {
+            return Ok(out);
+        }
+    }
+}
+
+/// Copies a block stored without compression.
+fn inflate_stored(reader: &mut BitReader, out: &mut Vec<u8>) -> Result<(), String> {
+    reader.align();
+    let len = try!(reader.read(16));
+    let nlen = try!(reader.read(16));
+    if len != !nlen & 0xFFFF {
+        return Err("corrupt stored deflate block".to_string());
+    }
+
+    for _ in 0..len {
+        out.push(try!(reader.read(8)) as u8);
+    }
+    Ok(())
+}
+
+/// Decodes the symbols of a compressed block until its end.
+fn inflate_block(reader: &mut BitReader, out: &mut Vec<u8>, lengths: &Huffman,
+                 distances: &Huffman) -> Result<(), String> {
+    loop {
+        let symbol = try!(lengths.decode(reader)) as usize;
+        if symbol < 256 {
+            out.push(symbol as u8);
+            continue;
+        }
+        if symbol == 256 {
+            return Ok(());
+        }
+
+        // A length and distance pair copies earlier output.
+        let symbol = symbol - 257;
+        if symbol >= LENGTH_BASE.len() {
+            return Err("invalid deflate length".to_string());
+        }
+        let len = LENGTH_BASE[symbol] as usize + try!(reader.read(LENGTH_EXTRA[symbol])) as usize;
+
+        let symbol = try!(distances.decode(reader)) as usize;
+        if symbol >= DIST_BASE.len() {
+            return Err("invalid deflate distance".to_string());
+        }
+        let dist = DIST_BASE[symbol] as usize + try!(reader.read(DIST_EXTRA[symbol])) as usize;
+        if dist > out.len() {
+            return Err("deflate distance reaches before the start of the data".to_string());
+        }
+
+        let start = out.len() - dist;
+        for i in 0..len {
+            let byte = out[start + i];
+            out.push(byte);
+        }
+    }
+}
+
+/// The codes used by blocks compressed with fixed Huffman codes.
+fn fixed_codes() -> (Huffman, Huffman) {
+    let mut lengths = [0; 288];
+    for (symbol, length) in lengths.iter_mut().enumerate() {
+        *length = match symbol {
+            0...143 => 8,
+            144...255 => 9,
+            256...279 => 7,
+            _ => 8,
+        };
+    }
+    (Huffman::new(&lengths), Huffman::new(&[5; 30]))
+}
+
+/// Reads the codes of a block compressed with dynamic Huffman codes, which
+/// are themselves stored as Huffman codes.
+fn dynamic_codes(reader: &mut BitReader) -> Result<(Huffman, Huffman), String> {
+    let num_lengths = try!(reader.read(5)) as usize + 257;
+    let num_distances = try!(reader.read(5)) as usize + 1;
+    let num_code_lengths = try!(reader.read(4)) as usize + 4;
+
+    let mut code_lengths = [0; 19];
+    for &index in CODE_LENGTH_ORDER.iter().take(num_code_lengths) {
+        code_lengths[index] = try!(reader.read(3)) as u8;
+    }
+    let code_length_code = Huffman::new(&code_lengths);
+
+    // Code lengths of both alphabets run together and may repeat across them.
+    let mut lengths = vec![0; num_lengths + num_distances];
+    let mut i = 0;
+    while i < lengths.len() {
+        let symbol = try!(code_length_code.decode(reader));
+        let (value, repeat) = match symbol {
+            0...15 => (symbol as u8, 1),
+            16 if i > 0 => (lengths[i - 1], 3 + try!(reader.read(2)) as usize),
+            17 => (0, 3 + try!(reader.read(3)) as usize),
+            18 => (0, 11 + try!(reader.read(7)) as usize),
+            _ => return Err("invalid deflate code lengths".to_string()),
+        };
+        if i + repeat > lengths.len() {
+            return Err("invalid deflate code lengths".to_string());
+        }
+        for _ in 0..repeat {
+            lengths[i] = value;
+            i += 1;
+        }
+    }
+
+    Ok((Huffman::new(&lengths[..num_lengths]), Huffman::new(&lengths[num_lengths..])))
+}
+
+/// A canonical Huffman code, stored as the number of codes of each length and
+/// the symbols in code order.
+struct Huffman {
+    counts: [u16; MAX_BITS + 1],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Huffman {
+        let mut counts = [0; MAX_BITS + 1];
+        for &length in lengths {
+            counts[length as usize] += 1;
+        }
+        counts[0] = 0;
+
+        let mut offsets = [0; MAX_BITS + 2];
+        for bits in 1..MAX_BITS + 1 {
+            offsets[bits + 1] = offsets[bits] + counts[bits];
+        }
+
+        let mut symbols = vec![0; offsets[MAX_BITS + 1] as usize];
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length != 0 {
+                symbols[offsets[length as usize] as usize] = symbol as u16;
+                offsets[length as usize] += 1;
+            }
+        }
+
+        Huffman {
+            counts: counts,
+            symbols: symbols,
+        }
+    }
+
+    /// Reads one symbol a bit at a time. Codes are stored most significant
+    /// bit first, unlike everything else in deflate.
+    fn decode(&self, reader: &mut BitReader) -> Result<u16, String> {
+        let mut code: i32 = 0;
+        let mut first: i32 = 0;
+        let mut index: i32 = 0;
+
+        for bits in 1..MAX_BITS + 1 {
+            code |= try!(reader.read(1)) as i32;
+            let count = self.counts[bits] as i32;
+            if code - count < first {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+
+        Err("invalid deflate code".to_string())
+    }
+}
+
+/// Reads values from bytes least significant bit first.
+struct BitReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    buffer: u32,
+    bits: u8,
+}
+
+impl<'a> BitReader<'a> {
+    fn read(&mut self, size: u8) -> Result<u32, String> {
+        while self.bits < size {
+            match self.bytes.get(self.pos) {
+                Some(&byte) => self.buffer |= (byte as u32) << self.bits,
+                None => return Err("unexpected end of deflate data".to_string()),
+            }
+            self.pos += 1;
+            self.bits += 8;
+        }
+
+        let value = self.buffer & ((1 << size) - 1);
+        self.buffer >>= size;
+        self.bits -= size;
+        Ok(value)
+    }
+
+    /// Skips to the start of the next byte.
+    fn align(&mut self) {
+        self.buffer = 0;
+        self.bits = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::inflate;
+
+    // "notch notch notch!" compressed by zlib without compression, with the
+    // fixed codes and, for a longer text, with codes of its own.
+    const STORED: [u8; 23] = [
+        0x01, 0x12, 0x00, 0xED, 0xFF, 0x6E, 0x6F, 0x74, 0x63, 0x68, 0x20, 0x6E,
+        0x6F, 0x74, 0x63, 0x68, 0x20, 0x6E, 0x6F, 0x74, 0x63, 0x68, 0x21,
+    ];
+    const FIXED: [u8; 11] = [0xCB, 0xCB, 0x2F, 0x49, 0xCE, 0x50, 0xC8, 0x43, 0x90, 0x8A, 0x00];
+    const DYNAMIC: [u8; 93] = [
+        0x6D, 0xCF, 0x4B, 0x0A, 0x80, 0x30, 0x0C, 0x45, 0xD1, 0xAD, 0x64, 0x01,
+        0x0E, 0x9A, 0xFE, 0x6C, 0x71, 0x35, 0x5A, 0x11, 0x3B, 0x55, 0xF7, 0x8F,
+        0x48, 0x5E, 0x06, 0x29, 0x8E, 0x02, 0x67, 0xF0, 0x2E, 0x71, 0x74, 0xF4,
+        0xFB, 0x9C, 0xC8, 0xD1, 0xD6, 0xAF, 0xFD, 0xBB, 0x6D, 0x7D, 0x16, 0x62,
+        0x30, 0x83, 0xBD, 0xB0, 0x07, 0x47, 0x70, 0x14, 0x0E, 0xE0, 0x0A, 0x66,
+        0xE1, 0xA8, 0x23, 0x19, 0x1E, 0xC4, 0x13, 0xBC, 0xD8, 0x66, 0x06, 0x7B,
+        0xDB, 0x9C, 0x75, 0x25, 0xD9, 0x68, 0x51, 0x0F, 0xB6, 0x5A, 0x47, 0x47,
+        0x95, 0xDD, 0x38, 0xA4, 0xBF, 0xF2, 0x7F, 0xF8, 0x05,
+    ];
+
+    /// The text compressed in DYNAMIC.
+    fn dynamic_text() -> Vec<u8> {
+        (0..12).map(|i| format!("{} fish, {} bird, {} cat; ", i, i * i % 17, i * 7 % 5))
+            .collect::<String>()
+            .into_bytes()
+    }
+
+    /// The type of the first block, from the bits after the final flag.
+    fn block_type(data: &[u8]) -> u8 {
+        data[0] >> 1 & 0x03
+    }
+
+    #[test]
+    fn inflates_stored_blocks() {
+        assert_eq!(block_type(&STORED), 0);
+        assert_eq!(inflate(&STORED).unwrap(), b"notch notch notch!".to_vec());
+    }
+
+    #[test]
+    fn inflates_blocks_with_fixed_codes() {
+        assert_eq!(block_type(&FIXED), 1);
+        assert_eq!(inflate(&FIXED).unwrap(), b"notch notch notch!".to_vec());
+    }
+
+    #[test]
+    fn inflates_blocks_with_dynamic_codes() {
+        assert_eq!(block_type(&DYNAMIC), 2);
+        assert_eq!(inflate(&DYNAMIC).unwrap(), dynamic_text());
+    }
+
+    #[test]
+    fn rejects_broken_streams() {
+        assert!(inflate(&DYNAMIC[..DYNAMIC.len() / 2]).is_err());
+        assert!(inflate(&FIXED[..4]).is_err());
+
+        // A final block of the reserved type 3.
+        assert!(inflate(&[0x07]).is_err());
+
+        // A stored block whose length does not match its complement.
+        let mut stored = STORED;
+        stored[3] ^= 0x01;
+        assert!(inflate(&stored).is_err());
+    }
+}
@@ -1,44 +1,324 @@
 use std::fmt;
+use std::path::{Path, PathBuf};
 
-use super::sdl2;
+use super::time;
 
-use super::graphics::Graphics;
-use super::memory::Memory;
+use super::display::Display;
+use super::flags::RplFlags;
+use super::frontend::{CpuState, Frontend, FrontendKind, Glyphs, HostEvent};
+use super::frontend::headless::Headless;
+use super::frontend::sdl::SdlFrontend;
+#[cfg(unix)]
+use super::frontend::terminal::Terminal;
+use super::memory::{Memory, MemoryLayout, Protection};
 use super::input::Input;
-use super::sound::Sound;
+use super::palette::Palette;
+use super::recorder::{AudioRecorder, Recorder};
+use super::screenshot;
+use super::settings::Settings;
 
 pub struct Interconnect {
     // Memory handles allocation along with reading and writing memory.
     pub memory: Memory,
 
-    // Graphics manages drawing with SDL.
-    pub graphics: Graphics,
+    // The display the application draws sprites to.
+    pub display: Display,
 
-    // Input handles SDL input events and the input state.
+    // Input holds the state of the keypad.
     pub input: Input,
 
-    // Sounds handles sound output through SDL.
-    pub sound: Sound,
+    // Registers saved by SCHIP programs, kept between sessions.
+    pub flags: RplFlags,
+
+    // Number of frames since the rom was loaded.
+    pub frame: u64,
+
+    // Memory layout roms are loaded with and how memory is protected.
+    layout: MemoryLayout,
+    protection: Protection,
+
+    // The frontend presents the display and collects input from the host.
+    frontend: Box<Frontend>,
+
+    // Colours used when saving screenshots.
+    palette: Palette,
+
+    // How many times larger than the display screenshots are saved.
+    screenshot_scale: usize,
+
+    // Screenshots still to be taken as (frame, path) pairs.
+    screenshots: Vec<(u64, PathBuf)>,
+
+    // Records every frame to a GIF or video file.
+    recorder: Option<Recorder>,
+
+    // Records the beep of every frame to a WAV file.
+    audio_recorder: Option<AudioRecorder>,
+
+    // The virtual machine halts after this many frames.
+    frame_limit: Option<u64>,
+
+    // Whether the beep is silenced, both through the frontend and in the
+    // audio recording.
+    muted: bool,
 }
 
 impl Interconnect {
-    pub fn new(rom: Vec<u8>) -> Interconnect {
-        // Setup SDL for graphics and audio.
-        let sdl_context = sdl2::init().unwrap();
-
+    pub fn new(rom: Vec<u8>, settings: &Settings) -> Result<Interconnect, String> {
         // Initialize all the peripherals needed by the virtual machine.
-        let memory = Memory::new(rom);
-        let graphics = Graphics::new(&sdl_context);
-        let input = Input::new(&sdl_context);
-        let sound = Sound::new(&sdl_context);
+        let layout = MemoryLayout {
+            platform: settings.platform,
+            load_address: settings.load_address,
+            font: settings.font,
+            font_offset: settings.font_offset,
+        };
+        let mut memory = try!(Memory::new(rom, &layout));
+        memory.set_protection(settings.protection);
+        let frontend: Box<Frontend> = match settings.frontend {
+            FrontendKind::Sdl => match SdlFrontend::new(settings) {
+                Ok(frontend) => Box::new(frontend),
+                Err(why) => return Err(format!("cannot open a window: {}", why)),
+            },
+            FrontendKind::Terminal(glyphs) => try!(terminal_frontend(settings, glyphs)),
+            FrontendKind::Headless => Box::new(Headless),
+        };
+
+        let display = Display::new();
+        let recorder = match settings.record {
+            Some(ref path) => {
+                match Recorder::create(path, &display, &settings.palette, settings.record_scale) {
+                    Ok(recorder) => Some(recorder),
+                    Err(why) => return Err(format!("cannot record to '{}': {}", path.display(), why)),
+                }
+            },
+            None => None,
+        };
+        let audio_recorder = match settings.record_audio {
+            Some(ref path) => match AudioRecorder::create(path, settings.tone) {
+                Ok(recorder) => Some(recorder),
+                Err(why) => return Err(format!("cannot record to '{}': {}", path.display(), why)),
+            },
+            None => None,
+        };
 
-        Interconnect {
+        Ok(Interconnect {
             memory: memory,
-            graphics: graphics,
-            input: input,
-            sound: sound,
+            display: display,
+            input: Input::new(),
+            flags: RplFlags::load(settings.flags_path.clone()),
+            frame: 0,
+            layout: layout,
+            protection: settings.protection,
+            frontend: frontend,
+            palette: settings.palette,
+            screenshot_scale: settings.screenshot_scale,
+            screenshots: settings.screenshots.clone(),
+            recorder: recorder,
+            audio_recorder: audio_recorder,
+            frame_limit: settings.frame_limit,
+            muted: settings.tone.mute,
+        })
+    }
+
+    /// Returns true if the virtual machine should run at real time speed.
+    pub fn is_realtime(&self) -> bool {
+        self.frontend.is_realtime()
+    }
+
+    /// Polls the frontend for input and sets the input state.
+    pub fn handle_input(&mut self) {
+        for event in self.frontend.poll_events() {
+            self.handle_event(event);
         }
     }
+
+    /// Reacts to an event from the frontend or another source of input such
+    /// as a script.
+    pub fn handle_event(&mut self, event: HostEvent) {
+        match event {
+            HostEvent::Key(key, down) => self.input.set_input(key, down),
+            HostEvent::Screenshot => {
+                let path = self.screenshot_path();
+                self.save_screenshot(&path);
+            },
+            HostEvent::Pause => {
+                // Silence the beep until the next frame runs.
+                self.input.paused = !self.input.paused;
+                if self.input.paused {
+                    self.frontend.set_beeping(self.frame, false);
+                }
+            },
+            HostEvent::Poke(addr, byte) => {
+                if self.input.paused && (addr as usize) < self.memory.as_slice().len() {
+                    self.memory.poke(addr as usize, byte);
+                }
+            },
+            HostEvent::Mute => {
+                self.muted = !self.muted;
+                self.frontend.set_muted(self.muted);
+                if let Some(ref mut recorder) = self.audio_recorder {
+                    recorder.set_muted(self.muted);
+                }
+            },
+            HostEvent::Quit => self.input.close_requested = true,
+        }
+    }
+
+    /// Pauses the virtual machine the way the user would, such as when a
+    /// protected write acts as a breakpoint. Frontends the user cannot resume
+    /// from halt instead. Returns true if the virtual machine was paused.
+    pub fn break_execution(&mut self) -> bool {
+        if !self.frontend.can_pause() {
+            self.input.close_requested = true;
+            return false;
+        }
+        if !self.input.paused {
+            self.handle_event(HostEvent::Pause);
+        }
+        true
+    }
+
+    /// Presents the display and updates the beep at the end of a frame.
+    pub fn end_frame(&mut self, beeping: bool, cpu: &CpuState) {
+        // The sound timer was just updated, so the beep changes from the start
+        // of the next frame.
+        self.frontend.set_beeping(self.frame + 1, beeping);
+        self.frontend.inspect(self.memory.as_slice(), cpu, self.input.paused);
+        self.frontend.present(&self.display, &self.input.input_state);
+        self.display.dirty = false;
+
+        self.take_screenshots();
+        self.record_frame();
+        self.record_audio(beeping);
+        self.frame += 1;
+
+        if self.frame_limit.map_or(false, |limit| self.frame >= limit) {
+            self.input.close_requested = true;
+        }
+
+        self.handle_input();
+    }
+
+    /// Presents the display and polls for input without running a frame,
+    /// for while the virtual machine is paused.
+    pub fn idle_frame(&mut self, cpu: &CpuState) {
+        self.frontend.inspect(self.memory.as_slice(), cpu, self.input.paused);
+        self.frontend.present(&self.display, &self.input.input_state);
+        self.handle_input();
+    }
+
+    /// Replaces the contents of memory with a new rom and blanks the display.
+    /// The frontend is left untouched so the window keeps its position and
+    /// the keypad keeps its current state. Roms that do not fit are refused
+    /// and the current one keeps running.
+    pub fn reset(&mut self, rom: Vec<u8>) -> Result<(), String> {
+        self.memory = try!(Memory::new(rom, &self.layout));
+        self.memory.set_protection(self.protection);
+        self.display.clear();
+        self.frame = 0;
+        self.frontend.restart_beep();
+        self.input.cancel_wait();
+        Ok(())
+    }
+
+    /// Adds the current frame to the recording. Recording stops if the file
+    /// cannot be written to.
+    fn record_frame(&mut self) {
+        let failed = match self.recorder {
+            Some(ref mut recorder) => match recorder.add_frame(&self.display) {
+                Ok(_) => false,
+                Err(why) => {
+                    println!("notch: cannot record to '{}': {}", recorder.path().display(), why);
+                    true
+                },
+            },
+            None => false,
+        };
+
+        if failed {
+            self.recorder = None;
+        }
+    }
+
+    /// Adds the audio of the current frame to the recording. Recording stops
+    /// if the file cannot be written to.
+    fn record_audio(&mut self, beeping: bool) {
+        let failed = match self.audio_recorder {
+            Some(ref mut recorder) => match recorder.add_frame(beeping) {
+                Ok(_) => false,
+                Err(why) => {
+                    println!("notch: cannot record to '{}': {}", recorder.path().display(), why);
+                    true
+                },
+            },
+            None => false,
+        };
+
+        if failed {
+            self.audio_recorder = None;
+        }
+    }
+
+    /// Saves the screenshots scheduled for the current frame. A headless
+    /// virtual machine that is not recording halts once the last one is taken.
+    fn take_screenshots(&mut self) {
+        if self.screenshots.is_empty() {
+            return;
+        }
+
+        let frame = self.frame;
+        let (due, pending): (Vec<_>, Vec<_>) = self.screenshots.drain(..)
+            .partition(|&(at, _)| at <= frame);
+        self.screenshots = pending;
+
+        for (_, path) in due {
+            self.save_screenshot(&path);
+        }
+
+        let recording = self.recorder.is_some() || self.audio_recorder.is_some();
+        if self.screenshots.is_empty() && !recording && !self.is_realtime() {
+            self.input.close_requested = true;
+        }
+    }
+
+    /// Names a screenshot after the time and frame it was taken at. Shots of
+    /// the same frame, such as while paused, are numbered so none are
+    /// overwritten.
+    fn screenshot_path(&self) -> PathBuf {
+        let stem = match time::strftime("notch-%Y%m%d-%H%M%S", &time::now()) {
+            Ok(stem) => format!("{}-{}", stem, self.frame),
+            Err(_) => format!("notch-{}", self.frame),
+        };
+
+        let mut path = PathBuf::from(format!("{}.png", stem));
+        let mut count = 1;
+        while path.exists() {
+            count += 1;
+            path = PathBuf::from(format!("{}-{}.png", stem, count));
+        }
+        path
+    }
+
+    fn save_screenshot(&self, path: &Path) {
+        match screenshot::save_png(path, &self.display, &self.palette, self.screenshot_scale) {
+            Ok(_) => println!("Saved screenshot: {}", path.display()),
+            Err(why) => println!("notch: cannot save screenshot '{}': {}", path.display(), why),
+        }
+    }
+}
+
+/// Sets up the terminal the virtual machine was started from as a frontend.
+#[cfg(unix)]
+fn terminal_frontend(settings: &Settings, glyphs: Glyphs) -> Result<Box<Frontend>, String> {
+    match Terminal::new(settings, glyphs) {
+        Ok(terminal) => Ok(Box::new(terminal)),
+        Err(why) => Err(format!("cannot use the terminal: {}", why)),
+    }
+}
+
+#[cfg(not(unix))]
+fn terminal_frontend(_: &Settings, _: Glyphs) -> Result<Box<Frontend>, String> {
+    Err("the terminal frontend is only supported on unix".to_string())
 }
 
 impl fmt::Debug for Interconnect {
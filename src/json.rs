@@ -0,0 +1,229 @@
+use std::collections::BTreeMap;
+
+/// A JSON value, as found in Octo cartridges and the CHIP-8 database.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(BTreeMap<String, Json>),
+}
+
+impl Json {
+    pub fn parse(text: &str) -> Result<Json, String> {
+        let chars: Vec<char> = text.chars().collect();
+        let mut parser = JsonParser { chars: &chars, pos: 0 };
+
+        let value = try!(parser.value());
+        parser.skip_whitespace();
+        if parser.pos < chars.len() {
+            return Err(format!("unexpected '{}' after the value", chars[parser.pos]));
+        }
+        Ok(value)
+    }
+
+    /// Returns a member of an object.
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match *self {
+            Json::Object(ref members) => members.get(key),
+            _ => None,
+        }
+    }
+}
+
+struct JsonParser<'a> {
+    chars: &'a [char],
+    pos: usize,
+}
+
+impl<'a> JsonParser<'a> {
+    fn skip_whitespace(&mut self) {
+        while self.pos < self.chars.len() && self.chars[self.pos].is_whitespace() {
+            self.pos += 1;
+        }
+    }
+
+    fn next(&mut self) -> Result<char, String> {
+        match self.chars.get(self.pos) {
+            Some(&c) => {
+                self.pos += 1;
+                Ok(c)
+            },
+            None => Err("unexpected end of JSON".to_string()),
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        self.skip_whitespace();
+        match try!(self.next()) {
+            c if c == expected => Ok(()),
+            c => Err(format!("expected '{}' but found '{}'", expected, c)),
+        }
+    }
+
+    fn literal(&mut self, word: &str, value: Json) -> Result<Json, String> {
+        for expected in word.chars() {
+            if try!(self.next()) != expected {
+                return Err(format!("expected '{}'", word));
+            }
+        }
+        Ok(value)
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        self.skip_whitespace();
+        match self.chars.get(self.pos).cloned() {
+            Some('{') => self.object(),
+            Some('[') => self.array(),
+            Some('"') => self.string().map(Json::String),
+            Some('t') => self.literal("true", Json::Bool(true)),
+            Some('f') => self.literal("false", Json::Bool(false)),
+            Some('n') => self.literal("null", Json::Null),
+            Some(c) if c == '-' || c.is_digit(10) => self.number(),
+            Some(c) => Err(format!("unexpected '{}'", c)),
+            None => Err("unexpected end of JSON".to_string()),
+        }
+    }
+
+    fn object(&mut self) -> Result<Json, String> {
+        let mut members = BTreeMap::new();
+        try!(self.expect('{'));
+        self.skip_whitespace();
+        if self.chars.get(self.pos) == Some(&'}') {
+            self.pos += 1;
+            return Ok(Json::Object(members));
+        }
+
+        loop {
+            self.skip_whitespace();
+            let key = try!(self.string());
+            try!(self.expect(':'));
+            let value = try!(self.value());
+            members.insert(key, value);
+
+            self.skip_whitespace();
+            match try!(self.next()) {
+                ',' => continue,
+                '}' => return Ok(Json::Object(members)),
+                c => return Err(format!("expected ',' or '}}' but found '{}'", c)),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Json, String> {
+        let mut elements = Vec::new();
+        try!(self.expect('['));
+        self.skip_whitespace();
+        if self.chars.get(self.pos) == Some(&']') {
+            self.pos += 1;
+            return Ok(Json::Array(elements));
+        }
+
+        loop {
+            elements.push(try!(self.value()));
+
+            self.skip_whitespace();
+            match try!(self.next()) {
+                ',' => continue,
+                ']' => return Ok(Json::Array(elements)),
+                c => return Err(format!("expected ',' or ']' but found '{}'", c)),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let mut string = String::new();
+        if try!(self.next()) != '"' {
+            return Err("expected a string".to_string());
+        }
+
+        loop {
+            match try!(self.next()) {
+                '"' => return Ok(string),
+                '\\' => {
+                    let c = match try!(self.next()) {
+                        'b' => '\u{8}',
+                        'f' => '\u{c}',
+                        'n' => '\n',
+                        'r' => '\r',
+                        't' => '\t',
+                        'u' => {
+                            let mut code = 0;
+                            for _ in 0..4 {
+                                match try!(self.next()).to_digit(16) {
+                                    Some(digit) => code = code * 16 + digit,
+                                    None => return Err("invalid unicode escape".to_string()),
+                                }
+                            }
+                            // Surrogate pairs are not combined.
+                            ::std::char::from_u32(code).unwrap_or('\u{fffd}')
+                        },
+                        c => c,
+                    };
+                    string.push(c);
+                },
+                c => string.push(c),
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<Json, String> {
+        let start = self.pos;
+        while let Some(&c) = self.chars.get(self.pos) {
+            if c.is_digit(10) || c == '-' || c == '+' || c == '.' || c == 'e' || c == 'E' {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+
+        let number: String = self.chars[start..self.pos].iter().cloned().collect();
+        number.parse::<f64>()
+            .map(Json::Number)
+            .map_err(|_| format!("invalid number '{}'", number))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::Json;
+
+    #[test]
+    fn parses_nested_values() {
+        let json = Json::parse(r#" {"a": [1, -2.5e1, true, null], "b": {"c": "d"}} "#).unwrap();
+        assert_eq!(json.get("a"), Some(&Json::Array(vec![
+            Json::Number(1.0), Json::Number(-25.0), Json::Bool(true), Json::Null,
+        ])));
+
+        let mut c = BTreeMap::new();
+        c.insert("c".to_string(), Json::String("d".to_string()));
+        assert_eq!(json.get("b"), Some(&Json::Object(c)));
+        assert_eq!(json.get("missing"), None);
+    }
+
+    #[test]
+    fn parses_empty_containers() {
+        assert_eq!(Json::parse("[]"), Ok(Json::Array(Vec::new())));
+        assert_eq!(Json::parse("{ }"), Ok(Json::Object(BTreeMap::new())));
+    }
+
+    #[test]
+    fn unescapes_strings() {
+        assert_eq!(Json::parse(r#""a\"b\\c\n\u0041""#),
+                   Ok(Json::String("a\"b\\c\nA".to_string())));
+    }
+
+    #[test]
+    fn rejects_malformed_json() {
+        assert!(Json::parse("{\"a\" 1}").is_err());
+        assert!(Json::parse("[1, 2").is_err());
+        assert!(Json::parse("\"unterminated").is_err());
+        assert!(Json::parse("tru").is_err());
+        assert!(Json::parse("1 2").is_err());
+        assert!(Json::parse("").is_err());
+    }
+}
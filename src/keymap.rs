@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+
+// Keys of the preset layouts, listed in the order of the CHIP-8 keys 0 to F
+// they are bound to.
+const LAYOUTS: [(&'static str, [&'static str; 16]); 2] = [
+    // Each key is labelled with the CHIP-8 key it presses.
+    ("hex", ["0", "1", "2", "3", "4", "5", "6", "7",
+             "8", "9", "a", "b", "c", "d", "e", "f"]),
+
+    // The left side of a QWERTY keyboard laid out like the COSMAC VIP's hex
+    // keypad:
+    //
+    //     1 2 3 C      1 2 3 4
+    //     4 5 6 D  ->  Q W E R
+    //     7 8 9 E      A S D F
+    //     A 0 B F      Z X C V
+    ("cosmac", ["x", "1", "2", "3", "q", "w", "e", "a",
+                "s", "d", "z", "c", "4", "r", "f", "v"]),
+];
+
+/// Translates host keys into keys on the CHIP-8 keypad. Host keys are
+/// identified by lowercase names such as "q", "space" or "up", so the same
+/// mapping works for every frontend.
+#[derive(Clone, Debug)]
+pub struct Keymap {
+    keys: HashMap<String, u8>,
+}
+
+impl Keymap {
+    /// Returns one of the preset layouts by name.
+    pub fn preset(name: &str) -> Option<Keymap> {
+        LAYOUTS.iter()
+            .find(|&&(layout, _)| layout == name)
+            .map(|&(_, ref host_keys)| {
+                let mut keymap = Keymap { keys: HashMap::new() };
+                for (key, host_key) in host_keys.iter().enumerate() {
+                    keymap.keys.insert(host_key.to_string(), key as u8);
+                }
+                keymap
+            })
+    }
+
+    /// Names of the preset layouts.
+    pub fn preset_names() -> Vec<&'static str> {
+        LAYOUTS.iter().map(|&(name, _)| name).collect()
+    }
+
+    /// Binds a CHIP-8 key given as a hex digit to a comma separated list of
+    /// host keys, replacing the keys it was bound to before.
+    ///
+    ///     5 = w, up
+    pub fn bind(&mut self, key: &str, host_keys: &str) -> Result<(), String> {
+        let key = try!(parse_key(key));
+
+        let unbound: Vec<String> = self.keys.iter()
+            .filter(|&(_, &bound)| bound == key)
+            .map(|(host_key, _)| host_key.clone())
+            .collect();
+        for host_key in unbound {
+            self.keys.remove(&host_key);
+        }
+
+        self.insert(key, host_keys)
+    }
+
+    /// Binds more host keys to a CHIP-8 key, keeping the ones it already has.
+    /// Host keys bound to another key are moved over.
+    pub fn add(&mut self, key: &str, host_keys: &str) -> Result<(), String> {
+        let key = try!(parse_key(key));
+        self.insert(key, host_keys)
+    }
+
+    fn insert(&mut self, key: u8, host_keys: &str) -> Result<(), String> {
+        for host_key in host_keys.split(',').map(|host_key| host_key.trim().to_lowercase()) {
+            if host_key.is_empty() {
+                return Err(format!("missing host key for keypad key {:X}", key));
+            }
+            self.keys.insert(host_key, key);
+        }
+
+        Ok(())
+    }
+
+    /// Returns the CHIP-8 key a host key is bound to.
+    pub fn get(&self, host_key: &str) -> Option<u8> {
+        self.keys.get(&host_key.to_lowercase()).cloned()
+    }
+}
+
+impl Default for Keymap {
+    fn default() -> Keymap {
+        Keymap::preset("hex").unwrap()
+    }
+}
+
+/// Parses a CHIP-8 key given as a hex digit.
+fn parse_key(key: &str) -> Result<u8, String> {
+    let digit = key.trim();
+    match u8::from_str_radix(digit, 16) {
+        Ok(key) if digit.len() == 1 => Ok(key),
+        _ => Err(format!("invalid keypad key '{}', expected 0 to F", digit)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Keymap;
+
+    #[test]
+    fn bind_replaces_the_keys_bound_before() {
+        let mut keymap = Keymap::default();
+        keymap.bind("5", "w, up").unwrap();
+        assert_eq!(keymap.get("5"), None);
+        assert_eq!(keymap.get("w"), Some(5));
+        assert_eq!(keymap.get("Up"), Some(5));
+    }
+
+    #[test]
+    fn add_keeps_the_keys_bound_before() {
+        let mut keymap = Keymap::default();
+        keymap.add("5", "pad dpup, pad a").unwrap();
+        assert_eq!(keymap.get("5"), Some(5));
+        assert_eq!(keymap.get("pad a"), Some(5));
+
+        // A host key moves to the key it is added to.
+        keymap.add("6", "pad a").unwrap();
+        assert_eq!(keymap.get("pad a"), Some(6));
+        assert_eq!(keymap.get("pad dpup"), Some(5));
+    }
+
+    #[test]
+    fn keys_must_be_hex_digits() {
+        let mut keymap = Keymap::default();
+        assert!(keymap.bind("10", "w").is_err());
+        assert!(keymap.add("g", "w").is_err());
+        assert!(keymap.add("5", "w,").is_err());
+    }
+}
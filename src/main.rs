@@ -1,31 +1,53 @@
 extern crate byteorder;
 extern crate getopts;
+extern crate libc;
 extern crate rand;
 extern crate sdl2;
 extern crate time;
 
 use std::env;
-use std::fs::File;
-use std::io::Read;
-use std::path::Path;
 
 use getopts::Options;
 
+mod cartridge;
+mod config;
 mod cpu;
+mod display;
+mod filter;
+mod flags;
+mod font;
+mod frontend;
+mod gif;
 mod graphics;
+mod inflate;
 mod input;
 mod interconnect;
+mod json;
+mod keymap;
 mod memory;
+mod memview;
+mod octo;
+mod palette;
+mod png;
+mod quirks;
+mod recorder;
+mod rom;
+mod romdb;
+mod screenshot;
+mod script;
+mod settings;
+mod sha1;
 mod sound;
 mod vm;
+mod watcher;
+mod wav;
+mod zip;
 
 fn main() {
     let args: Vec<String> = env::args().collect();
 
     // Initialize the argument parser and parse them.
-    let mut opts = Options::new();
-    opts.optflag("v", "version", "print version information");
-    opts.optflag("h", "help", "Print this message");
+    let opts = options();
     let matches = match opts.parse(&args[1..]) {
         Ok(m) => m,
         Err(f) => {
@@ -45,7 +67,8 @@ fn main() {
         return;
     }
 
-    // Assume the first free argument is the rom filename.
+    // Assume the first free argument is the rom filename. Roms can also be
+    // read from standard input or picked from a zip archive.
     let rom_file_name = if !matches.free.is_empty() {
         matches.free[0].clone()
     } else {
@@ -53,31 +76,147 @@ fn main() {
         std::process::exit(1);
     };
 
-    let rom = read_bin(&rom_file_name);
-    println!("Loading rom: {}", rom_file_name);
+    if rom_file_name == "-" {
+        if matches.opt_str("script").as_ref().map(|path| path.as_str()) == Some("-") {
+            println!("notch: the rom and the script cannot both be read from standard input");
+            std::process::exit(1);
+        }
+        if matches.opt_present("w") {
+            println!("notch: cannot watch a rom read from standard input");
+            std::process::exit(1);
+        }
+    }
+
+    // Naming an archive on its own lists the roms to choose from.
+    let rom::Rom { program: rom, options } = match rom::read(&rom_file_name) {
+        Ok(rom::Contents::Rom(rom)) => rom,
+        Ok(rom::Contents::Archive(listing)) => {
+            println!("{}", listing);
+            return;
+        },
+        Err(why) => {
+            println!("notch: {}", why);
+            std::process::exit(2);
+        },
+    };
+
+    // Known roms are identified by their hash to pick up their settings.
+    let rom_database = match romdb::RomDatabase::load() {
+        Ok(rom_database) => rom_database,
+        Err(why) => {
+            println!("notch: {}", why);
+            std::process::exit(1);
+        },
+    };
+    let mut rom_entry = rom_database.lookup(&rom);
+    for &(ref key, ref value) in options.iter() {
+        rom_entry.set_default(key, value);
+    }
+
+    // Settings on the command line override those for the rom, which
+    // override those in the config file.
+    let config = load_config(matches.opt_str("c"));
+    let settings = match settings::Settings::load(&config, &rom_entry, &matches) {
+        Ok(settings) => settings,
+        Err(why) => {
+            println!("notch: {}", why);
+            std::process::exit(1);
+        },
+    };
+
+    match settings.title {
+        Some(ref title) => println!("Loading rom: {} ({})", rom_file_name, title),
+        None => println!("Loading rom: {} (sha1 {})", rom_file_name, rom_entry.sha1),
+    }
 
     // Initialize the virtual machine and boot the rom.
-    let mut vm = vm::VirtualMachine::new(rom);
+    let mut vm = match vm::VirtualMachine::new(rom, &settings) {
+        Ok(vm) => vm,
+        Err(why) => {
+            println!("notch: {}", why);
+            std::process::exit(2);
+        },
+    };
+    if matches.opt_present("w") {
+        vm.watch(&rom_file_name);
+    }
     vm.run();
 }
 
-/// Reads a file into a vector of unsigned bytes.
-fn read_bin<P: AsRef<Path>>(path: P) -> Vec<u8> {
-    let mut buffer: Vec<u8> = Vec::new();
-    let filename = format!("{}", path.as_ref().display());
+/// Returns the command line options notch understands.
+fn options() -> Options {
+    let mut opts = Options::new();
+    opts.optflag("v", "version", "print version information");
+    opts.optflag("h", "help", "Print this message");
+    opts.optflag("w", "watch", "reload the rom when it changes on disk");
+    opts.optopt("c", "config", "read settings from FILE", "FILE");
+    opts.optopt("p", "palette", "display colours as a theme name or hex colours \
+                                 starting with the background", "THEME|BG,FG[,C2,C3]");
+    opts.optopt("", "filter", "reduce flicker by blending frames: none, or, or \
+                               decay with a strength from 0 up to 1", "none|or|decay[:N]");
+    opts.optopt("s", "scale", "initial window size as a multiple of the display", "N");
+    opts.optopt("", "scaling", "scale the display to fit the window or by whole \
+                                numbers only", "fit|integer");
+    opts.optflag("f", "fullscreen", "start in fullscreen, toggle with F11");
+    opts.optflag("", "keypad", "show a clickable keypad next to the display, toggle \
+                                with F10");
+    opts.optflag("", "memory-viewer", "open a window with a live hex dump of memory, \
+                                       toggle with F8");
+    opts.optflag("", "headless", "run without a window, audio or input");
+    opts.optopt("k", "keymap", "keyboard layout for the keypad: hex (keys 0-9 and A-F) \
+                                or cosmac (1234/QWER/ASDF/ZXCV)", "LAYOUT");
+    opts.optopt("", "script", "play back keypad input from a script file, or - for \
+                               standard input", "FILE");
+    opts.optflag("", "no-audio", "do not open an audio device, the beep is silent");
+    opts.optopt("", "frequency", "pitch of the beep in hertz", "HZ");
+    opts.optopt("", "waveform", "shape of the beep", "square|sine|triangle|sawtooth|noise");
+    opts.optopt("", "volume", "loudness of the beep from 0 to 1", "N");
+    opts.optflag("m", "mute", "do not play the beep");
+    opts.optflag("t", "terminal", "draw the display in the terminal instead of a window");
+    opts.optopt("", "glyphs", "characters used to draw in the terminal: half blocks \
+                               in colour or braille dots at higher density", "half|braille");
+    opts.optmulti("", "screenshot-at-frame", "save a PNG of the display at a frame, \
+                                              F12 saves one at any time", "FRAME:FILE");
+    opts.optopt("", "screenshot-scale", "size of screenshots as a multiple of the \
+                                         display", "N");
+    opts.optopt("r", "record", "record the display to an animated GIF or a \
+                                YUV4MPEG2 video (.gif or .y4m)", "FILE");
+    opts.optopt("", "record-scale", "size of recordings as a multiple of the display", "N");
+    opts.optopt("", "record-audio", "record the beep to a WAV file", "FILE");
+    opts.optopt("", "frames", "stop after running N frames", "N");
+    opts.optopt("", "platform", "interpreter the rom was written for, which sets \
+                                 its quirks", "chip8|schip|xochip");
+    opts.optopt("", "quirks", "comma separated quirks to enable: shift, load-store, \
+                               jump, vf-reset, clip, or none", "LIST");
+    opts.optopt("", "instructions-per-frame", "instructions to run every 60Hz frame", "N");
+    opts.optopt("", "font", "hex digit font: schip, vip, dream6800, eti660, fish, or a \
+                             file of 80 bytes", "NAME|FILE");
+    opts.optopt("", "font-offset", "where the font is stored in memory, such as 0x50", "ADDR");
+    opts.optopt("", "protect", "catch writes to reserved memory or executed code: report \
+                                them, pause the program, or refuse them", "off|warn|break|fault");
+    opts.optopt("", "load-address", "where the rom is loaded and starts running, such as \
+                                     0x600 for ETI-660 programs", "ADDR");
+    opts
+}
 
-    // Open and read the file if it exists.
-    match File::open(path) {
-        Ok(ref mut file) => {
-            file.read_to_end(&mut buffer).unwrap();
+/// Loads the config file passed on the command line or the one in the default
+/// location. A missing default config file is not an error.
+fn load_config(path: Option<String>) -> config::Config {
+    let result = match path {
+        Some(path) => config::Config::load(path),
+        None => match config::Config::default_path() {
+            Some(ref path) if path.exists() => config::Config::load(path),
+            _ => Ok(config::Config::default()),
         },
+    };
+
+    match result {
+        Ok(config) => config,
         Err(why) => {
-            println!("notch: cannot open '{}': {}", filename, why);
+            println!("notch: {}", why);
             std::process::exit(2);
         },
-    };
-
-    buffer
+    }
 }
 
 /// Prints the application name alongside the cargo version.
@@ -89,7 +228,7 @@ fn print_version() {
 fn print_usage(opts: Options) {
     println!("Notch is a CHIP-8 virtual machine written in Rust.");
     println!("");
-    println!("{}", opts.usage("Usage: notch [OPTIONS] ROM"));
+    println!("{}", opts.usage("Usage: notch [OPTIONS] ROM|ARCHIVE.zip[:ROM]|-"));
     println!("To contribute or report bugs, please see:");
     println!("<https://github.com/Reshurum/notch>");
 }
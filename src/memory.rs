@@ -1,9 +1,10 @@
-use super::byteorder::{BigEndian, ByteOrder};
 
-// Size of the memory map of a CHIP-8 interpreter is 4kB.
-pub const RAM_SIZE: usize = 4096;
+use super::font::{Font, CHARACTER_SIZE, FONT_SIZE};
+use super::quirks::Platform;
 
-// Memory map constraints for CHIP-8 virtual machines.
+// Memory map constraints for CHIP-8 virtual machines. Programs are loaded
+// after the reserved space unless they were written for an interpreter that
+// loads them elsewhere, such as 0x600 on the ETI-660.
 pub const END_RESERVED: usize = 0x200;
 
 // Here are some additional unused constrains for documentation purposes. These
@@ -13,104 +14,295 @@ pub const END_RESERVED: usize = 0x200;
 //     pub const START_RESERVED: usize = 0x000;
 //     pub const END_PROGRAM_SPACE: usize = 0xFFF;
 
-// Font size constants.
-const CHARACTER_SIZE: usize = 5;
-const CHARACTER_COUNT: usize = 16;
+/// What happens when a program writes to reserved interpreter memory or over
+/// code it has already executed. Such writes usually come from a runaway I
+/// register, though some programs modify their own code on purpose.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Protection {
+    // Writes go through unchecked.
+    Off,
 
-// Where fonts are stored in interpreter memory.
-const FONT_OFFSET: usize = 0;
+    // Writes go through and are reported, once for each address.
+    Warn,
+
+    // Writes go through and the first to each address pauses the program
+    // with the state of the cpu printed, the way hitting a breakpoint would.
+    Break,
+
+    // Writes are refused, leaving memory unchanged, and reported once for
+    // each address.
+    Fault,
+}
+
+impl Protection {
+    pub fn parse(mode: &str) -> Result<Protection, String> {
+        match mode.trim() {
+            "off" => Ok(Protection::Off),
+            "warn" => Ok(Protection::Warn),
+            "break" => Ok(Protection::Break),
+            "fault" => Ok(Protection::Fault),
+            _ => Err(format!("invalid protection '{}', expected off, warn, break or fault", mode)),
+        }
+    }
+}
+
+impl Default for Protection {
+    fn default() -> Protection {
+        Protection::Off
+    }
+}
+
+/// A protected write caught by memory protection.
+#[derive(Clone, Copy, Debug)]
+pub struct Violation {
+    pub addr: usize,
+
+    // True if the address was executed rather than reserved.
+    pub executed: bool,
+}
+
+/// Where things go in memory when a rom is loaded.
+#[derive(Clone, Copy, Debug)]
+pub struct MemoryLayout {
+    // Platforms differ in how much memory they have.
+    pub platform: Platform,
+
+    // Where the rom is loaded.
+    pub load_address: usize,
+
+    // The font and where it is stored in reserved memory.
+    pub font: Font,
+    pub font_offset: usize,
+}
 
 pub struct Memory {
     // Memory allocated for the running CHIP-8 application.
     ram: Vec<u8>,
+
+    // Where fonts are stored in interpreter memory.
+    font_offset: usize,
+
+    // Protection of the memory below the rom and of executed addresses,
+    // which are marked as instructions are fetched.
+    protection: Protection,
+    reserved_end: usize,
+    executed: Vec<bool>,
+
+    // Addresses already reported, and writes caught since they were last
+    // taken.
+    reported: Vec<bool>,
+    violations: Vec<Violation>,
 }
 
 impl Memory {
-    /// Allocate some memory for ram and load a rom into it.
-    pub fn new(rom: Vec<u8>) -> Memory {
-        // Allocate 4kB of memory as defined in the many specifications.
-        let mut ram = vec![0; RAM_SIZE];
+    /// Allocate memory for a platform and load a rom and font into it. Fails
+    /// if the rom does not fit or overlaps the font.
+    pub fn new(rom: Vec<u8>, layout: &MemoryLayout) -> Result<Memory, String> {
+        let platform = layout.platform;
+        let load_address = layout.load_address;
+
+        // Allocate 4kB of memory as defined in the many specifications, or
+        // 64kB for XO-CHIP.
+        let size = platform.memory_size();
+        if load_address >= size {
+            return Err(format!("load address {:#x} is outside of the {} bytes of {} memory",
+                               load_address, size, platform.name()));
+        }
+        if rom.len() > size - load_address {
+            return Err(format!("rom is {} bytes, but {} programs loaded at {:#x} can be at \
+                                most {} bytes", rom.len(), platform.name(), load_address,
+                               size - load_address));
+        }
+        if layout.font_offset + FONT_SIZE > load_address {
+            return Err(format!("font at {:#x} overlaps the rom loaded at {:#x}",
+                               layout.font_offset, load_address));
+        }
 
-        Memory::dump_rom(&mut ram, &rom);
-        Memory::dump_fonts(&mut ram);
+        let mut ram = vec![0; size];
+        Memory::dump_rom(&mut ram, &rom, load_address);
+        Memory::dump_font(&mut ram, &layout.font, layout.font_offset);
 
-        Memory {
+        Ok(Memory {
             ram: ram,
-        }
+            font_offset: layout.font_offset,
+            protection: Protection::Off,
+            reserved_end: load_address,
+            executed: vec![false; size],
+            reported: vec![false; size],
+            violations: Vec::new(),
+        })
     }
 
-    /// Simply returns an 8-bit word at the specified address.
+    /// Simply returns an 8-bit word at the specified address. Addresses past
+    /// the end of memory wrap around to the start, as does every access
+    /// below, so a runaway I or program counter cannot read out of bounds.
     #[inline(always)]
     pub fn read(&self, addr: usize) -> u8 {
-        self.ram[addr]
+        self.ram[addr % self.ram.len()]
     }
 
     /// Write an 8-bit byte at a specific address. There is no concern over
-    /// endianess since this function operates on single bytes.
+    /// endianess since this function operates on single bytes. Writes to
+    /// protected addresses are recorded as violations.
     #[inline(always)]
     pub fn write(&mut self, addr: usize, byte: u8) {
+        let addr = addr % self.ram.len();
+        if self.protection != Protection::Off &&
+           (addr < self.reserved_end || self.executed[addr]) {
+            if !self.reported[addr] {
+                self.reported[addr] = true;
+                self.violations.push(Violation {
+                    addr: addr,
+                    executed: addr >= self.reserved_end,
+                });
+            }
+            if self.protection == Protection::Fault {
+                return;
+            }
+        }
+
         self.ram[addr] = byte;
     }
 
+    /// Writes a byte on behalf of the user rather than the program, such as
+    /// from the memory viewer. Protection does not apply.
+    pub fn poke(&mut self, addr: usize, byte: u8) {
+        let addr = addr % self.ram.len();
+        self.ram[addr] = byte;
+    }
+
+    /// The whole of memory, for inspecting it without going through the cpu.
+    pub fn as_slice(&self) -> &[u8] {
+        &self.ram
+    }
+
     /// Reads a 16-bit word from ram. This function is used mainly to read and
     /// execute instructions as their word size is 16 bits.
     #[inline(always)]
     pub fn read_word(&self, addr: u16) -> u16 {
-        BigEndian::read_u16(&self.ram[addr as usize..])
+        let addr = addr as usize;
+        (self.read(addr) as u16) << 8 | self.read(addr + 1) as u16
+    }
+
+    /// Reads the instruction at an address for execution, marking it as
+    /// executed for memory protection.
+    #[inline(always)]
+    pub fn fetch(&mut self, addr: u16) -> u16 {
+        let size = self.executed.len();
+        let addr = addr as usize;
+        self.executed[addr % size] = true;
+        self.executed[(addr + 1) % size] = true;
+        self.read_word(addr as u16)
+    }
+
+    pub fn set_protection(&mut self, protection: Protection) {
+        self.protection = protection;
+    }
+
+    pub fn protection(&self) -> Protection {
+        self.protection
+    }
+
+    /// Returns the protected writes caught since the last call.
+    pub fn take_violations(&mut self) -> Vec<Violation> {
+        ::std::mem::replace(&mut self.violations, Vec::new())
     }
 
     /// Find the memory address of the requested character.
     #[inline(always)]
     pub fn get_font(&self, font: u8) -> u16 {
-        FONT_OFFSET as u16 + font as u16 * CHARACTER_SIZE as u16
+        self.font_offset as u16 + font as u16 * CHARACTER_SIZE as u16
     }
 
     /// Dumps a passed rom containing executable code into ram starting at
-    /// program space (right after reserved space ends).
-    fn dump_rom(ram: &mut Vec<u8>, rom: &Vec<u8>) {
+    /// the load address, usually right after reserved space ends.
+    fn dump_rom(ram: &mut Vec<u8>, rom: &Vec<u8>, load_address: usize) {
         // Dump the rom containing the executable code of the program into ram
         // starting at the start of the program space.
         for i in 0..rom.len() {
-            ram[i + END_RESERVED] = rom[i];
+            ram[i + load_address] = rom[i];
         }
     }
 
-    /// Dumps the standard CHIP-8 fonts to ram. The fonts are stored at the
-    /// start of reserved memory and this is fine since the fonts are the only
-    /// thing being stored in reserved memory.
-    fn dump_fonts(ram: &mut Vec<u8>) {
-        // The characters 0-F to be stored in ram as a font for chip 8 programs.
-        // Nested vectors are used for ease of reading.
-        //
-        // Hopefully this is optimized by the compiler, but it's no problem
-        // since this is run only once.
-        let fonts = vec![
-            vec![0xF0, 0x90, 0x90, 0x90, 0xF0], // 0
-            vec![0x20, 0x60, 0x20, 0x20, 0x70], // 1
-            vec![0xF0, 0x10, 0xf0, 0x80, 0xF0], // 2
-            vec![0xF0, 0x10, 0xF0, 0x10, 0xF0], // 3
-            vec![0x90, 0x90, 0xF0, 0x10, 0x10], // 4
-            vec![0xF0, 0x80, 0xF0, 0x10, 0xF0], // 5
-            vec![0xF0, 0x80, 0xF0, 0x90, 0xF0], // 6
-            vec![0xF0, 0x10, 0x20, 0x40, 0x40], // 7
-            vec![0xF0, 0x90, 0xF0, 0x90, 0xF0], // 8
-            vec![0xF0, 0x90, 0xF0, 0x10, 0xF0], // 9
-            vec![0xF0, 0x90, 0xF0, 0x90, 0x90], // A
-            vec![0xE0, 0x90, 0xE0, 0x90, 0xE0], // B
-            vec![0xF0, 0x80, 0x80, 0x80, 0xF0], // C
-            vec![0xE0, 0x90, 0x90, 0x90, 0xE0], // D
-            vec![0xF0, 0x80, 0xF0, 0x80, 0xF0], // E
-            vec![0xF0, 0x80, 0xF0, 0x80, 0x80], // F
-        ];
-
-        for i in 0..CHARACTER_COUNT {
+    /// Dumps a font to ram. The font is stored in reserved memory, usually at
+    /// its start, and this is fine since the font is the only thing being
+    /// stored in reserved memory.
+    fn dump_font(ram: &mut Vec<u8>, font: &Font, font_offset: usize) {
+        for (i, glyph) in font.glyphs.iter().enumerate() {
             // Find where the current character should be stored in memory.
-            let start: usize = FONT_OFFSET + i * CHARACTER_SIZE;
+            let start: usize = font_offset + i * CHARACTER_SIZE;
 
             // Copy the current character into the calculated spot in memory.
-            for j in 0..CHARACTER_SIZE {
-                ram[start + j] = fonts[i][j];
-            }
+            ram[start..start + CHARACTER_SIZE].copy_from_slice(glyph);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Memory, MemoryLayout, Protection};
+    use super::super::font::Font;
+    use super::super::quirks::Platform;
+
+    /// A rom that fills the memory of CHIP-8 from 0x200 to the very end.
+    fn full_memory() -> Memory {
+        let mut rom = vec![0; 0x1000 - 0x200];
+        rom[0xDFE] = 0x12;
+        rom[0xDFF] = 0x34;
+        let layout = MemoryLayout {
+            platform: Platform::Chip8,
+            load_address: 0x200,
+            font: Font::default(),
+            font_offset: 0,
+        };
+        Memory::new(rom, &layout).unwrap()
+    }
+
+    #[test]
+    fn fetches_the_last_instruction_of_memory() {
+        let mut memory = full_memory();
+        assert_eq!(memory.fetch(0xFFE), 0x1234);
+
+        // An instruction straddling the end takes its second byte from the
+        // start of memory, where the font is.
+        assert_eq!(memory.fetch(0xFFF), 0x34F0);
+        assert_eq!(memory.read_word(0x1000), memory.read_word(0));
+    }
+
+    #[test]
+    fn wraps_reads_and_writes_past_the_end() {
+        let mut memory = full_memory();
+        memory.write(0x1000 + 0x300, 0xAB);
+        assert_eq!(memory.read(0x300), 0xAB);
+        assert_eq!(memory.read(0x1300), 0xAB);
+
+        memory.poke(0x1FFF, 0xCD);
+        assert_eq!(memory.as_slice()[0xFFF], 0xCD);
+    }
+
+    #[test]
+    fn protects_wrapped_addresses() {
+        let mut memory = full_memory();
+        memory.set_protection(Protection::Fault);
+        let font = memory.read(0x10);
+        memory.write(0x1000 + 0x10, !font);
+        assert_eq!(memory.read(0x10), font);
+        assert_eq!(memory.take_violations()[0].addr, 0x10);
+    }
+
+    #[test]
+    fn reports_each_refused_address_once() {
+        let mut memory = full_memory();
+        memory.set_protection(Protection::Fault);
+        for _ in 0..3 {
+            memory.write(0x10, 0xFF);
+            memory.write(0x11, 0xFF);
         }
+
+        let addresses: Vec<usize> = memory.take_violations().iter().map(|v| v.addr).collect();
+        assert_eq!(addresses, vec![0x10, 0x11]);
+
+        memory.write(0x10, 0xFF);
+        assert!(memory.take_violations().is_empty());
     }
 }
@@ -0,0 +1,400 @@
+use super::sdl2::keyboard::Keycode;
+use super::sdl2::pixels::{Color, PixelFormatEnum};
+use super::sdl2::rect::Rect;
+use super::sdl2::render::Texture;
+use super::sdl2;
+
+use super::frontend::{CpuState, HostEvent};
+use super::graphics::LABEL_GLYPHS;
+use super::palette::{Palette, Rgb};
+use super::settings::Settings;
+
+// Bytes per pixel in the RGB24 streaming texture.
+const BYTES_PER_PIXEL: usize = 3;
+
+// Bytes shown on each row of the dump and how many rows fit in the window.
+const BYTES_PER_ROW: usize = 16;
+const VISIBLE_ROWS: usize = 32;
+
+// Characters are the 4x5 hex digits the keypad is labelled with, with a pixel
+// between characters and two between rows.
+const CHARACTER_WIDTH: usize = 5;
+const ROW_HEIGHT: usize = 7;
+
+// Each row starts with a four digit address and a space, followed by each
+// byte as two digits and a space.
+const ADDRESS_CHARACTERS: usize = 5;
+const BYTE_CHARACTERS: usize = 3;
+
+// Size of the dump in pixels, with a pixel of border around it.
+const WIDTH: usize = 1 + (ADDRESS_CHARACTERS + BYTES_PER_ROW * BYTE_CHARACTERS) * CHARACTER_WIDTH;
+const HEIGHT: usize = 1 + VISIBLE_ROWS * ROW_HEIGHT;
+
+// Frames a byte keeps flashing after it was written.
+const FLASH_FRAMES: u8 = 30;
+
+// Highlights for the bytes the cpu points at.
+const PC_COLOR: Rgb = Rgb(0x20, 0x60, 0xC0);
+const I_COLOR: Rgb = Rgb(0x20, 0xA0, 0x40);
+const STACK_COLOR: Rgb = Rgb(0xA0, 0x60, 0x20);
+const WRITE_COLOR: Rgb = Rgb(0xC0, 0x20, 0x20);
+
+/// A second window showing a live hex dump of memory. The bytes at the
+/// program counter, I and the calls on the stack are highlighted and bytes
+/// flash as they are written. While the virtual machine is paused bytes can be
+/// selected and edited by typing hex digits.
+pub struct MemoryViewer {
+    renderer: sdl2::render::Renderer<'static>,
+
+    // Streaming texture the dump is drawn into, scaled up to the window.
+    texture: Texture,
+
+    // True while the window is shown.
+    visible: bool,
+
+    // First row of the dump shown at the top of the window.
+    top_row: usize,
+
+    // Size of memory when it was last shown.
+    size: usize,
+
+    // Memory as of the previous frame and how many frames each byte has left
+    // to flash since it last changed.
+    previous: Vec<u8>,
+    flash: Vec<u8>,
+
+    // The selected byte and the high digit typed into it so far.
+    cursor: Option<usize>,
+    pending: Option<u8>,
+
+    // True while the virtual machine is paused, when edits are allowed.
+    paused: bool,
+
+    // Text and background colours.
+    palette: Palette,
+}
+
+impl MemoryViewer {
+    pub fn new(sdl_context: &sdl2::Sdl, settings: &Settings) -> sdl2::SdlResult<MemoryViewer> {
+        let video_subsystem = try!(sdl_context.video());
+
+        // The window is created hidden up front and shown when toggled.
+        let mut builder = video_subsystem.window("Notch - Memory",
+                                                 WIDTH as u32 * 3,
+                                                 HEIGHT as u32 * 3);
+        builder.resizable();
+        if !settings.memory_viewer {
+            builder.hidden();
+        }
+        let window = try!(builder.build());
+
+        let renderer = try!(window.renderer().build());
+        let texture = try!(renderer.create_texture_streaming(PixelFormatEnum::RGB24,
+                                                             (WIDTH as u32, HEIGHT as u32)));
+
+        Ok(MemoryViewer {
+            renderer: renderer,
+            texture: texture,
+            visible: settings.memory_viewer,
+            top_row: 0,
+            size: 0,
+            previous: Vec::new(),
+            flash: Vec::new(),
+            cursor: None,
+            pending: None,
+            paused: false,
+            palette: settings.palette,
+        })
+    }
+
+    /// The SDL id of the window, for telling which window events belong to.
+    pub fn window_id(&self) -> Option<u32> {
+        self.renderer.window().map(|window| window.id())
+    }
+
+    /// Shows or hides the window. The dump scrolls to the program counter
+    /// when shown.
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+        self.pending = None;
+
+        if let Some(window) = self.renderer.window_mut() {
+            if self.visible {
+                window.show();
+                window.raise();
+            } else {
+                window.hide();
+            }
+        }
+
+        // Forget the old dump so the next frame starts over, scrolling to the
+        // program counter.
+        self.size = 0;
+    }
+
+    /// Updates the dump with the state of memory at the end of a frame. The
+    /// frame is skipped if the dump cannot be uploaded.
+    pub fn present(&mut self, memory: &[u8], cpu: &CpuState, paused: bool) -> sdl2::SdlResult<()> {
+        if paused != self.paused {
+            self.paused = paused;
+            self.pending = None;
+            let title = if paused { "Notch - Memory (paused)" } else { "Notch - Memory" };
+            if let Some(window) = self.renderer.window_mut() {
+                window.set_title(title);
+            }
+        }
+
+        if !self.visible {
+            return Ok(());
+        }
+
+        // Start over when shown or when memory changes size.
+        if memory.len() != self.size {
+            self.size = memory.len();
+            self.previous = memory.to_vec();
+            self.flash = vec![0; memory.len()];
+            self.cursor = None;
+            self.scroll_to(cpu.pc as usize);
+        }
+
+        for (addr, &byte) in memory.iter().enumerate() {
+            if byte != self.previous[addr] {
+                self.previous[addr] = byte;
+                self.flash[addr] = FLASH_FRAMES;
+            } else if self.flash[addr] > 0 {
+                self.flash[addr] -= 1;
+            }
+        }
+
+        try!(self.upload_dump(memory, cpu));
+
+        let viewport = self.viewport().map(|(x, y, scale)| {
+            Rect::new(x, y, (WIDTH as f32 * scale) as u32, (HEIGHT as f32 * scale) as u32)
+                .unwrap_or(None)
+        }).unwrap_or(None);
+        self.renderer.set_draw_color(Color::RGB(0, 0, 0));
+        self.renderer.clear();
+        self.renderer.copy(&self.texture, None, viewport);
+        self.renderer.present();
+        Ok(())
+    }
+
+    /// Scrolls the dump by a number of rows, up for negative amounts.
+    pub fn scroll(&mut self, rows: i32) {
+        let last_row = (self.row_count() as i32 - VISIBLE_ROWS as i32).max(0);
+        self.top_row = (self.top_row as i32 + rows).max(0).min(last_row) as usize;
+    }
+
+    /// Selects the byte at a point in the window.
+    pub fn click(&mut self, x: i32, y: i32) {
+        self.pending = None;
+        self.cursor = self.byte_at(x, y);
+    }
+
+    /// Handles a key pressed while the window has focus. Arrow keys move the
+    /// selection and page keys scroll. Hex digits edit the selected byte
+    /// while paused, the second digit of a byte writing it to memory.
+    pub fn key_down(&mut self, keycode: Keycode, host_events: &mut Vec<HostEvent>) {
+        let page = VISIBLE_ROWS as i32;
+        match keycode {
+            Keycode::PageUp => self.scroll(-page),
+            Keycode::PageDown => self.scroll(page),
+            Keycode::Home => self.scroll(-(self.row_count() as i32)),
+            Keycode::End => self.scroll(self.row_count() as i32),
+            Keycode::Up => self.move_cursor(-(BYTES_PER_ROW as i32)),
+            Keycode::Down => self.move_cursor(BYTES_PER_ROW as i32),
+            Keycode::Left => self.move_cursor(-1),
+            Keycode::Right => self.move_cursor(1),
+            Keycode::Escape => {
+                self.pending = None;
+                self.cursor = None;
+            },
+            _ => {
+                let digit = match u8::from_str_radix(&keycode.name(), 16) {
+                    Ok(digit) if keycode.name().len() == 1 => digit,
+                    _ => return,
+                };
+                let addr = match self.cursor {
+                    Some(addr) if self.paused => addr,
+                    _ => return,
+                };
+
+                match self.pending.take() {
+                    None => self.pending = Some(digit),
+                    Some(high) => {
+                        host_events.push(HostEvent::Poke(addr as u16, high << 4 | digit));
+                        self.move_cursor(1);
+                    },
+                }
+            },
+        }
+    }
+
+    /// Moves the selection, scrolling to keep it in view.
+    fn move_cursor(&mut self, offset: i32) {
+        self.pending = None;
+        if let Some(addr) = self.cursor {
+            let addr = (addr as i32 + offset).max(0).min(self.size as i32 - 1);
+            self.cursor = Some(addr as usize);
+            self.scroll_to(addr as usize);
+        }
+    }
+
+    /// Scrolls just far enough for an address to be in view.
+    fn scroll_to(&mut self, addr: usize) {
+        let row = addr / BYTES_PER_ROW;
+        if row < self.top_row {
+            self.top_row = row;
+        } else if row >= self.top_row + VISIBLE_ROWS {
+            self.top_row = row + 1 - VISIBLE_ROWS;
+        }
+    }
+
+    fn row_count(&self) -> usize {
+        (self.size + BYTES_PER_ROW - 1) / BYTES_PER_ROW
+    }
+
+    /// Returns the address of the byte drawn at a point in the window.
+    fn byte_at(&self, x: i32, y: i32) -> Option<usize> {
+        let (viewport_x, viewport_y, scale) = match self.viewport() {
+            Some(viewport) => viewport,
+            None => return None,
+        };
+
+        let x = ((x - viewport_x) as f32 / scale).floor() as i32 - 1;
+        let y = ((y - viewport_y) as f32 / scale).floor() as i32 - 1;
+        if x < 0 || y < 0 || x as usize >= WIDTH - 1 || y as usize >= HEIGHT - 1 {
+            return None;
+        }
+
+        // Only the digits of a byte count, not the address or spaces.
+        let character = x as usize / CHARACTER_WIDTH;
+        if character < ADDRESS_CHARACTERS ||
+           (character - ADDRESS_CHARACTERS) % BYTE_CHARACTERS == BYTE_CHARACTERS - 1 {
+            return None;
+        }
+        let column = (character - ADDRESS_CHARACTERS) / BYTE_CHARACTERS;
+        let row = self.top_row + y as usize / ROW_HEIGHT;
+
+        let addr = row * BYTES_PER_ROW + column;
+        if addr < self.size { Some(addr) } else { None }
+    }
+
+    /// Calculates where the dump is drawn in the window as its top left
+    /// corner and scale, keeping its aspect ratio.
+    fn viewport(&self) -> Option<(i32, i32, f32)> {
+        let (output_width, output_height) = match self.renderer.output_size() {
+            Ok(size) => size,
+            Err(_) => return None,
+        };
+
+        let scale_x = output_width as f32 / WIDTH as f32;
+        let scale_y = output_height as f32 / HEIGHT as f32;
+        let scale = scale_x.min(scale_y);
+
+        let x = (output_width as f32 - WIDTH as f32 * scale) / 2.0;
+        let y = (output_height as f32 - HEIGHT as f32 * scale) / 2.0;
+        Some((x as i32, y as i32, scale))
+    }
+
+    /// Draws the visible rows of the dump into the streaming texture.
+    fn upload_dump(&mut self, memory: &[u8], cpu: &CpuState) -> sdl2::SdlResult<()> {
+        let mut pixels = vec![self.palette.background(); WIDTH * HEIGHT];
+
+        for row in 0..VISIBLE_ROWS {
+            let start = (self.top_row + row) * BYTES_PER_ROW;
+            if start >= memory.len() {
+                break;
+            }
+            let y = 1 + row * ROW_HEIGHT;
+
+            let foreground = self.palette.foreground();
+            let background = self.palette.background();
+            let address_color = background.blend(foreground, 0.5);
+            for digit in 0..4 {
+                let nybble = (start >> (12 - digit * 4)) as u8 & 0xF;
+                self.draw_digit(&mut pixels, 1 + digit * CHARACTER_WIDTH, y, nybble, address_color);
+            }
+
+            for column in 0..BYTES_PER_ROW {
+                let addr = start + column;
+                if addr >= memory.len() {
+                    break;
+                }
+                let x = 1 + (ADDRESS_CHARACTERS + column * BYTE_CHARACTERS) * CHARACTER_WIDTH;
+
+                let (highlight, text) = self.byte_colors(addr, cpu);
+                if let Some(highlight) = highlight {
+                    fill(&mut pixels, x - 1, y - 1, CHARACTER_WIDTH * 2 + 1, ROW_HEIGHT, highlight);
+                }
+
+                // The selected byte shows the digit typed so far.
+                let byte = memory[addr];
+                let high = match self.pending {
+                    Some(digit) if self.cursor == Some(addr) => digit,
+                    _ => byte >> 4,
+                };
+                self.draw_digit(&mut pixels, x, y, high, text);
+                self.draw_digit(&mut pixels, x + CHARACTER_WIDTH, y, byte & 0xF, text);
+            }
+        }
+
+        self.texture.with_lock(None, |buffer: &mut [u8], pitch: usize| {
+            for y in 0..HEIGHT {
+                for x in 0..WIDTH {
+                    let Rgb(r, g, b) = pixels[y * WIDTH + x];
+                    let pos = pitch * y + BYTES_PER_PIXEL * x;
+
+                    buffer[pos] = r;
+                    buffer[pos + 1] = g;
+                    buffer[pos + 2] = b;
+                }
+            }
+        })
+    }
+
+    /// Picks the highlight and text colour of a byte. The selection is drawn
+    /// inverted, then the program counter, I and the stack take precedence
+    /// over bytes flashing from a write.
+    fn byte_colors(&self, addr: usize, cpu: &CpuState) -> (Option<Rgb>, Rgb) {
+        let foreground = self.palette.foreground();
+        let background = self.palette.background();
+        let pc = cpu.pc as usize;
+        let i = cpu.i as usize;
+
+        if self.cursor == Some(addr) {
+            (Some(foreground), background)
+        } else if addr == pc || addr == pc + 1 {
+            (Some(PC_COLOR), foreground)
+        } else if addr == i {
+            (Some(I_COLOR), foreground)
+        } else if cpu.stack.iter().any(|&call| addr == call as usize || addr == call as usize + 1) {
+            (Some(STACK_COLOR), foreground)
+        } else if self.flash[addr] > 0 {
+            let level = self.flash[addr] as f32 / FLASH_FRAMES as f32;
+            (Some(background.blend(WRITE_COLOR, level)), foreground)
+        } else {
+            (None, foreground)
+        }
+    }
+
+    /// Draws a hex digit with its top left corner at a pixel.
+    fn draw_digit(&self, pixels: &mut [Rgb], x: usize, y: usize, digit: u8, color: Rgb) {
+        for (line, &bits) in LABEL_GLYPHS[digit as usize].iter().enumerate() {
+            for bit in 0..4 {
+                if bits & (0x80 >> bit) != 0 {
+                    pixels[(y + line) * WIDTH + x + bit] = color;
+                }
+            }
+        }
+    }
+}
+
+/// Fills a rectangle of the dump with a colour.
+fn fill(pixels: &mut [Rgb], x: usize, y: usize, width: usize, height: usize, color: Rgb) {
+    for row in y..(y + height).min(HEIGHT) {
+        for column in x..(x + width).min(WIDTH) {
+            pixels[row * WIDTH + column] = color;
+        }
+    }
+}
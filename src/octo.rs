@@ -0,0 +1,852 @@
+use std::collections::HashMap;
+
+// Octo assembles programs to run from the same address as any other rom.
+const START: usize = 0x200;
+
+// Largest address an XO-CHIP program can reach.
+const MAX_ADDRESS: usize = 0xFFFF;
+
+/// Places in the program holding an address that was used before its label
+/// was defined, filled in once the whole program has been read.
+enum Fixup {
+    // The low 12 bits of an instruction.
+    Address,
+
+    // Two bytes holding a full 16-bit address.
+    Long,
+
+    // The values loaded into v0 and v1 by ":unpack", with the nybble put in
+    // front of the address, or the whole address for ":unpack long".
+    Unpack(Option<u8>),
+}
+
+/// Control flow still waiting for the address it leads to.
+enum Block {
+    // The jump past an "if ... begin" block, taken when the condition fails.
+    If(usize),
+
+    // The jump past the "else" half of a block.
+    Else(usize),
+
+    // The start of a loop and the jumps out of it made by "while".
+    Loop(usize, Vec<usize>),
+}
+
+/// Assembles the subset of Octo programs are written in outside of macros:
+/// instructions, labels, constants, register aliases, raw bytes and the
+/// structured "if", "loop" and "while" statements.
+///
+///     : main
+///       i := sprite
+///       loop
+///         sprite v0 v1 5
+///         v0 += 1
+///       again
+///     : sprite 0xF0 0x90 0x90 0x90 0xF0
+///
+/// Macros, ":calc" and ":stringmode" are refused, since those programs are
+/// better exported from Octo as a .ch8. So are instructions notch cannot run:
+/// XO-CHIP's bitplanes and audio patterns, the large SCHIP font and machine
+/// code routines.
+pub fn assemble(source: &str) -> Result<Vec<u8>, String> {
+    let mut tokens = Vec::new();
+    for (number, line) in source.lines().enumerate() {
+        let code = line.split('#').next().unwrap_or("");
+        for token in code.split_whitespace() {
+            tokens.push((number + 1, token));
+        }
+    }
+
+    let mut assembler = Assembler {
+        tokens: tokens,
+        pos: 0,
+        rom: Vec::new(),
+        here: START,
+        labels: HashMap::new(),
+        constants: HashMap::new(),
+        aliases: HashMap::new(),
+        fixups: Vec::new(),
+        blocks: Vec::new(),
+        next: None,
+        main_first: false,
+    };
+
+    // Room for a jump to main, dropped if main comes first.
+    try!(assembler.instruction(0x0000));
+
+    while assembler.pos < assembler.tokens.len() {
+        try!(assembler.statement());
+    }
+    assembler.finish()
+}
+
+struct Assembler<'a> {
+    // Tokens of the program and the line each came from.
+    tokens: Vec<(usize, &'a str)>,
+    pos: usize,
+
+    // The assembled program, starting from START, and the address of the
+    // next byte.
+    rom: Vec<u8>,
+    here: usize,
+
+    labels: HashMap<&'a str, usize>,
+    constants: HashMap<&'a str, i64>,
+    aliases: HashMap<&'a str, u8>,
+
+    // Addresses to fill in, with the label they wait for and its line.
+    fixups: Vec<(usize, Fixup, &'a str, usize)>,
+
+    // Open "if ... begin" blocks and loops, innermost last.
+    blocks: Vec<Block>,
+
+    // A label from ":next" for the second byte of the next instruction.
+    next: Option<&'a str>,
+
+    // Whether "main" was the first label, so no jump to it is needed.
+    main_first: bool,
+}
+
+impl<'a> Assembler<'a> {
+    fn statement(&mut self) -> Result<(), String> {
+        let token = try!(self.next_token());
+
+        if let Some(x) = self.register(token) {
+            return self.register_statement(x);
+        }
+        if let Some(value) = number(token) {
+            return self.byte(value);
+        }
+
+        match token {
+            ":" => {
+                let name = try!(self.name());
+                if name == "main" && self.here == START + 2 && self.rom.len() == 2 {
+                    self.rom.clear();
+                    self.here = START;
+                    self.main_first = true;
+                }
+                let here = self.here;
+                self.define(name, here)
+            },
+            ":next" => {
+                self.next = Some(try!(self.name()));
+                Ok(())
+            },
+            ":const" => {
+                let name = try!(self.name());
+                let token = try!(self.next_token());
+                let value = try!(self.value(token));
+                self.constants.insert(name, value);
+                Ok(())
+            },
+            ":alias" => {
+                let name = try!(self.name());
+                let x = try!(self.expect_register());
+                self.aliases.insert(name, x);
+                Ok(())
+            },
+            ":org" => {
+                let token = try!(self.next_token());
+                let address = try!(self.value(token));
+                if address < START as i64 || address > MAX_ADDRESS as i64 {
+                    return Err(self.error(format!("cannot place code at {:#x}", address)));
+                }
+                self.here = address as usize;
+                Ok(())
+            },
+            ":byte" => {
+                let token = try!(self.next_token());
+                let value = try!(self.value(token));
+                self.byte(value)
+            },
+            ":pointer" => {
+                let address = try!(self.address(Fixup::Long));
+                self.emit((address >> 8) as u8).and_then(|_| self.emit(address as u8))
+            },
+            ":unpack" => {
+                let nybble = match try!(self.next_token()) {
+                    "long" => None,
+                    token => Some(try!(self.nybble(token))),
+                };
+                let address = try!(self.address(Fixup::Unpack(nybble)));
+                let (high, low) = match nybble {
+                    Some(nybble) => ((nybble as usize) << 4 | address >> 8, address & 0xFF),
+                    None => (address >> 8, address & 0xFF),
+                };
+                try!(self.instruction(0x6000 | high as u16));
+                self.instruction(0x6100 | low as u16)
+            },
+            ":call" => {
+                let address = try!(self.address(Fixup::Address));
+                self.instruction(0x2000 | address as u16)
+            },
+            ":breakpoint" => self.next_token().map(|_| ()),
+            ":monitor" => self.next_token().and_then(|_| self.next_token()).map(|_| ()),
+            ":macro" | ":calc" | ":stringmode" | ":assert" => {
+                Err(self.error(format!("'{}' is not supported, export the program from Octo \
+                                        as a .ch8 instead", token)))
+            },
+            "plane" | "audio" | "pitch" | "native" => {
+                Err(self.error(format!("'{}' is not supported by notch", token)))
+            },
+
+            "return" | ";" => self.instruction(0x00EE),
+            "clear" => self.instruction(0x00E0),
+            "scroll-right" => self.instruction(0x00FB),
+            "scroll-left" => self.instruction(0x00FC),
+            "exit" => self.instruction(0x00FD),
+            "lores" => self.instruction(0x00FE),
+            "hires" => self.instruction(0x00FF),
+            "scroll-down" => {
+                let n = try!(self.next_nybble());
+                self.instruction(0x00C0 | n)
+            },
+            "scroll-up" => {
+                let n = try!(self.next_nybble());
+                self.instruction(0x00D0 | n)
+            },
+            "bcd" => self.register_instruction(0xF033),
+            "saveflags" => self.register_instruction(0xF075),
+            "loadflags" => self.register_instruction(0xF085),
+            "save" => self.save_or_load(0xF055, 0x5002),
+            "load" => self.save_or_load(0xF065, 0x5003),
+            "sprite" => {
+                let x = try!(self.expect_register()) as u16;
+                let y = try!(self.expect_register()) as u16;
+                let n = try!(self.next_nybble());
+                self.instruction(0xD000 | x << 8 | y << 4 | n)
+            },
+            "jump" => {
+                let address = try!(self.address(Fixup::Address));
+                self.instruction(0x1000 | address as u16)
+            },
+            "jump0" => {
+                let address = try!(self.address(Fixup::Address));
+                self.instruction(0xB000 | address as u16)
+            },
+            "delay" => self.timer_statement(0xF015),
+            "buzzer" => self.timer_statement(0xF018),
+            "i" => self.i_statement(),
+
+            "if" => {
+                // The condition is followed by "then" or "begin", which
+                // decides how it is compiled.
+                let operator = try!(self.peek(1));
+                let length = if operator == "key" || operator == "-key" { 2 } else { 3 };
+                let terminator = try!(self.peek(length));
+
+                match terminator {
+                    "then" => {
+                        try!(self.condition(false));
+                        self.pos += 1;
+                        Ok(())
+                    },
+                    "begin" => {
+                        try!(self.condition(true));
+                        self.pos += 1;
+                        let at = self.here;
+                        self.blocks.push(Block::If(at));
+                        self.instruction(0x1000)
+                    },
+                    _ => Err(self.error(format!("expected 'then' or 'begin', found '{}'", terminator))),
+                }
+            },
+            "else" => match self.blocks.pop() {
+                Some(Block::If(at)) => {
+                    let jump = self.here;
+                    try!(self.instruction(0x1000));
+                    let here = self.here;
+                    self.patch_jump(at, here);
+                    self.blocks.push(Block::Else(jump));
+                    Ok(())
+                },
+                _ => Err(self.error("'else' without 'if ... begin'".to_string())),
+            },
+            "end" => match self.blocks.pop() {
+                Some(Block::If(at)) | Some(Block::Else(at)) => {
+                    let here = self.here;
+                    self.patch_jump(at, here);
+                    Ok(())
+                },
+                _ => Err(self.error("'end' without 'begin'".to_string())),
+            },
+            "loop" => {
+                let here = self.here;
+                self.blocks.push(Block::Loop(here, Vec::new()));
+                Ok(())
+            },
+            "while" => {
+                try!(self.condition(true));
+                let at = self.here;
+                let exits = self.blocks.iter_mut().rev().filter_map(|block| match *block {
+                    Block::Loop(_, ref mut exits) => Some(exits),
+                    _ => None,
+                }).next();
+                match exits {
+                    Some(exits) => exits.push(at),
+                    None => return Err(self.error("'while' outside of a loop".to_string())),
+                }
+                self.instruction(0x1000)
+            },
+            "again" => match self.blocks.pop() {
+                Some(Block::Loop(start, exits)) => {
+                    try!(self.instruction(0x1000 | start as u16));
+                    let here = self.here;
+                    for at in exits {
+                        self.patch_jump(at, here);
+                    }
+                    Ok(())
+                },
+                _ => Err(self.error("'again' without 'loop'".to_string())),
+            },
+
+            // Anything else names a subroutine to call.
+            _ => {
+                self.pos -= 1;
+                let address = try!(self.address(Fixup::Address));
+                self.instruction(0x2000 | address as u16)
+            },
+        }
+    }
+
+    /// Compiles a statement starting with a register, such as "v0 += 1".
+    fn register_statement(&mut self, x: u8) -> Result<(), String> {
+        let x = x as u16;
+        let operator = try!(self.next_token());
+        let operand = try!(self.next_token());
+
+        if let Some(y) = self.register(operand) {
+            let y = y as u16;
+            let op = match operator {
+                ":=" => 0x0,
+                "|=" => 0x1,
+                "&=" => 0x2,
+                "^=" => 0x3,
+                "+=" => 0x4,
+                "-=" => 0x5,
+                ">>=" => 0x6,
+                "=-" => 0x7,
+                "<<=" => 0xE,
+                _ => return Err(self.error(format!("unknown operator '{}'", operator))),
+            };
+            return self.instruction(0x8000 | x << 8 | y << 4 | op);
+        }
+
+        match (operator, operand) {
+            (":=", "key") => self.instruction(0xF00A | x << 8),
+            (":=", "delay") => self.instruction(0xF007 | x << 8),
+            (":=", "random") => {
+                let token = try!(self.next_token());
+                let mask = try!(self.byte_value(token));
+                self.instruction(0xC000 | x << 8 | mask as u16)
+            },
+            (":=", _) => {
+                let value = try!(self.byte_value(operand));
+                self.instruction(0x6000 | x << 8 | value as u16)
+            },
+            ("+=", _) => {
+                let value = try!(self.byte_value(operand));
+                self.instruction(0x7000 | x << 8 | value as u16)
+            },
+            ("-=", _) => {
+                let value = try!(self.byte_value(operand));
+                self.instruction(0x7000 | x << 8 | (value as u16).wrapping_neg() & 0xFF)
+            },
+            _ => Err(self.error(format!("cannot use '{}' with '{}'", operator, operand))),
+        }
+    }
+
+    /// Compiles a statement starting with "i".
+    fn i_statement(&mut self) -> Result<(), String> {
+        match try!(self.next_token()) {
+            ":=" => match try!(self.peek(0)) {
+                "hex" => {
+                    self.pos += 1;
+                    self.register_instruction(0xF029)
+                },
+                "bighex" => Err(self.error("'bighex' is not supported by notch".to_string())),
+                "long" => {
+                    self.pos += 1;
+                    try!(self.instruction(0xF000));
+                    let address = try!(self.address(Fixup::Long));
+                    self.emit((address >> 8) as u8).and_then(|_| self.emit(address as u8))
+                },
+                _ => {
+                    let address = try!(self.address(Fixup::Address));
+                    self.instruction(0xA000 | address as u16)
+                },
+            },
+            "+=" => self.register_instruction(0xF01E),
+            operator => Err(self.error(format!("cannot use '{}' with 'i'", operator))),
+        }
+    }
+
+    /// Compiles "delay := vx" and the like.
+    fn timer_statement(&mut self, op: u16) -> Result<(), String> {
+        match try!(self.next_token()) {
+            ":=" => self.register_instruction(op),
+            operator => Err(self.error(format!("expected ':=', found '{}'", operator))),
+        }
+    }
+
+    /// Compiles "save vx" or XO-CHIP's "save vx - vy", and the same for load.
+    fn save_or_load(&mut self, op: u16, range_op: u16) -> Result<(), String> {
+        let x = try!(self.expect_register()) as u16;
+        if self.peek(0) == Ok("-") {
+            self.pos += 1;
+            let y = try!(self.expect_register()) as u16;
+            return self.instruction(range_op | x << 8 | y << 4);
+        }
+        self.instruction(op | x << 8)
+    }
+
+    /// Compiles an instruction that takes a single register in its X nybble.
+    fn register_instruction(&mut self, op: u16) -> Result<(), String> {
+        let x = try!(self.expect_register()) as u16;
+        self.instruction(op | x << 8)
+    }
+
+    /// Compiles a condition as the instructions that skip the next one when
+    /// it does not hold, or when it does if `negate` is set. Comparisons
+    /// other than equality go through vf, as in Octo.
+    fn condition(&mut self, negate: bool) -> Result<(), String> {
+        let x = try!(self.expect_register()) as u16;
+        let operator = try!(self.next_token());
+        let operator = if !negate {
+            operator
+        } else {
+            match operator {
+                "==" => "!=",
+                "!=" => "==",
+                "key" => "-key",
+                "-key" => "key",
+                "<" => ">=",
+                ">=" => "<",
+                ">" => "<=",
+                "<=" => ">",
+                _ => operator,
+            }
+        };
+
+        match operator {
+            "key" => return self.instruction(0xE0A1 | x << 8),
+            "-key" => return self.instruction(0xE09E | x << 8),
+            _ => {},
+        }
+
+        let operand = try!(self.next_token());
+        let y = self.register(operand).map(|y| y as u16);
+        match (operator, y) {
+            ("==", Some(y)) => self.instruction(0x9000 | x << 8 | y << 4),
+            ("!=", Some(y)) => self.instruction(0x5000 | x << 8 | y << 4),
+            ("==", None) => {
+                let value = try!(self.byte_value(operand));
+                self.instruction(0x4000 | x << 8 | value as u16)
+            },
+            ("!=", None) => {
+                let value = try!(self.byte_value(operand));
+                self.instruction(0x3000 | x << 8 | value as u16)
+            },
+            ("<", _) | (">", _) | ("<=", _) | (">=", _) => {
+                match y {
+                    Some(y) => try!(self.instruction(0x8F00 | y << 4)),
+                    None => {
+                        let value = try!(self.byte_value(operand));
+                        try!(self.instruction(0x6F00 | value as u16));
+                    },
+                }
+
+                // vf := vx - vf or vf := vf - vx, leaving the borrow flag.
+                let (subtract, skip) = match operator {
+                    "<" => (0x8F07, 0x4F00),
+                    ">=" => (0x8F07, 0x3F00),
+                    ">" => (0x8F05, 0x4F00),
+                    _ => (0x8F05, 0x3F00),
+                };
+                try!(self.instruction(subtract | x << 4));
+                self.instruction(skip)
+            },
+            _ => Err(self.error(format!("unknown comparison '{}'", operator))),
+        }
+    }
+
+    /// Fills in the jump at `at` now that its destination is known.
+    fn patch_jump(&mut self, at: usize, address: usize) {
+        let index = at - START;
+        self.rom[index] = 0x10 | (address >> 8) as u8 & 0x0F;
+        self.rom[index + 1] = address as u8;
+    }
+
+    /// Reads an address, leaving a fixup for labels that are not defined
+    /// yet. The fixup refers to the instruction about to be written.
+    fn address(&mut self, fixup: Fixup) -> Result<usize, String> {
+        let token = try!(self.next_token());
+        if let Some(&address) = self.labels.get(token) {
+            return self.check_address(address as i64, &fixup);
+        }
+        if number(token).is_some() || self.constants.contains_key(token) {
+            let value = try!(self.value(token));
+            return self.check_address(value, &fixup);
+        }
+        if self.register(token).is_some() {
+            return Err(self.error(format!("expected an address, found '{}'", token)));
+        }
+
+        let line = self.tokens[self.pos - 1].0;
+        self.fixups.push((self.here, fixup, token, line));
+        Ok(0)
+    }
+
+    fn check_address(&self, address: i64, fixup: &Fixup) -> Result<usize, String> {
+        let max = match *fixup {
+            Fixup::Address | Fixup::Unpack(Some(_)) => 0xFFF,
+            Fixup::Long | Fixup::Unpack(None) => MAX_ADDRESS,
+        };
+        if address < 0 || address > max as i64 {
+            return Err(self.error(format!("address {:#x} is out of reach", address)));
+        }
+        Ok(address as usize)
+    }
+
+    /// Reads a number, constant or defined label.
+    fn value(&self, token: &str) -> Result<i64, String> {
+        if let Some(value) = number(token) {
+            return Ok(value);
+        }
+        if let Some(&value) = self.constants.get(token) {
+            return Ok(value);
+        }
+        if let Some(&address) = self.labels.get(token) {
+            return Ok(address as i64);
+        }
+        Err(self.error(format!("unknown name '{}'", token)))
+    }
+
+    fn byte_value(&self, token: &str) -> Result<u8, String> {
+        let value = try!(self.value(token));
+        if value < -128 || value > 255 {
+            return Err(self.error(format!("{} does not fit in a byte", value)));
+        }
+        Ok(value as u8)
+    }
+
+    fn nybble(&self, token: &str) -> Result<u8, String> {
+        let value = try!(self.value(token));
+        if value < 0 || value > 15 {
+            return Err(self.error(format!("{} does not fit in a nybble", value)));
+        }
+        Ok(value as u8)
+    }
+
+    fn next_nybble(&mut self) -> Result<u16, String> {
+        let token = try!(self.next_token());
+        self.nybble(token).map(|n| n as u16)
+    }
+
+    fn register(&self, token: &str) -> Option<u8> {
+        if let Some(&x) = self.aliases.get(token) {
+            return Some(x);
+        }
+        let mut chars = token.chars();
+        match (chars.next(), chars.next(), chars.next()) {
+            (Some('v'), Some(digit), None) | (Some('V'), Some(digit), None) => {
+                digit.to_digit(16).map(|x| x as u8)
+            },
+            _ => None,
+        }
+    }
+
+    fn expect_register(&mut self) -> Result<u8, String> {
+        let token = try!(self.next_token());
+        match self.register(token) {
+            Some(x) => Ok(x),
+            None => Err(self.error(format!("expected a register, found '{}'", token))),
+        }
+    }
+
+    /// Reads the name given to a label, constant or alias.
+    fn name(&mut self) -> Result<&'a str, String> {
+        let name = try!(self.next_token());
+        if number(name).is_some() || self.register(name).is_some() {
+            return Err(self.error(format!("'{}' cannot be used as a name", name)));
+        }
+        Ok(name)
+    }
+
+    fn define(&mut self, name: &'a str, address: usize) -> Result<(), String> {
+        if self.labels.insert(name, address).is_some() {
+            return Err(self.error(format!("'{}' is defined twice", name)));
+        }
+        Ok(())
+    }
+
+    fn byte(&mut self, value: i64) -> Result<(), String> {
+        if value < -128 || value > 255 {
+            return Err(self.error(format!("{} does not fit in a byte", value)));
+        }
+        self.emit(value as u8)
+    }
+
+    fn instruction(&mut self, op: u16) -> Result<(), String> {
+        if let Some(name) = self.next.take() {
+            let address = self.here + 1;
+            try!(self.define(name, address));
+        }
+        try!(self.emit((op >> 8) as u8));
+        self.emit(op as u8)
+    }
+
+    fn emit(&mut self, byte: u8) -> Result<(), String> {
+        if self.here > MAX_ADDRESS {
+            return Err(self.error("program does not fit in memory".to_string()));
+        }
+        let index = self.here - START;
+        if index >= self.rom.len() {
+            self.rom.resize(index + 1, 0);
+        }
+        self.rom[index] = byte;
+        self.here += 1;
+        Ok(())
+    }
+
+    fn next_token(&mut self) -> Result<&'a str, String> {
+        let token = try!(self.peek(0));
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn peek(&self, offset: usize) -> Result<&'a str, String> {
+        match self.tokens.get(self.pos + offset) {
+            Some(&(_, token)) => Ok(token),
+            None => Err(self.error("program ends in the middle of a statement".to_string())),
+        }
+    }
+
+    /// Prefixes an error with the line of the last token read.
+    fn error(&self, message: String) -> String {
+        match self.tokens.get(self.pos.saturating_sub(1)) {
+            Some(&(line, _)) => format!("line {}: {}", line, message),
+            None => message,
+        }
+    }
+
+    /// Fills in the addresses of labels used before they were defined and
+    /// the jump to main.
+    fn finish(mut self) -> Result<Vec<u8>, String> {
+        if !self.blocks.is_empty() {
+            return Err("program ends inside a block or loop".to_string());
+        }
+
+        let fixups = ::std::mem::replace(&mut self.fixups, Vec::new());
+        for (at, fixup, name, line) in fixups {
+            let address = match self.labels.get(name) {
+                Some(&address) => address,
+                None => return Err(format!("line {}: unknown name '{}'", line, name)),
+            };
+            let index = at - START;
+            match fixup {
+                Fixup::Address => {
+                    if address > 0xFFF {
+                        return Err(format!("line {}: address {:#x} is out of reach", line, address));
+                    }
+                    self.rom[index] |= (address >> 8) as u8;
+                    self.rom[index + 1] = address as u8;
+                },
+                Fixup::Long => {
+                    self.rom[index] = (address >> 8) as u8;
+                    self.rom[index + 1] = address as u8;
+                },
+                Fixup::Unpack(nybble) => {
+                    let high = match nybble {
+                        Some(nybble) if address <= 0xFFF => nybble << 4 | (address >> 8) as u8,
+                        Some(_) => return Err(format!("line {}: address {:#x} is out of reach",
+                                                      line, address)),
+                        None => (address >> 8) as u8,
+                    };
+                    self.rom[index + 1] = high;
+                    self.rom[index + 3] = address as u8;
+                },
+            }
+        }
+
+        if !self.main_first {
+            let main = match self.labels.get("main") {
+                Some(&main) => main,
+                None => return Err("program has no main label".to_string()),
+            };
+            self.patch_jump(START, main);
+        }
+        Ok(self.rom)
+    }
+}
+
+/// Reads a decimal, hex (0x) or binary (0b) number, which may be negative.
+fn number(token: &str) -> Option<i64> {
+    let (negative, digits) = if token.starts_with('-') {
+        (true, &token[1..])
+    } else {
+        (false, token)
+    };
+
+    let value = if digits.starts_with("0x") || digits.starts_with("0X") {
+        i64::from_str_radix(&digits[2..], 16)
+    } else if digits.starts_with("0b") || digits.starts_with("0B") {
+        i64::from_str_radix(&digits[2..], 2)
+    } else if digits.chars().next().map_or(false, |c| c.is_digit(10)) {
+        digits.parse::<i64>()
+    } else {
+        return None;
+    };
+
+    value.ok().map(|value| if negative { -value } else { value })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::assemble;
+
+    #[test]
+    fn main_first_needs_no_jump() {
+        assert_eq!(assemble(": main clear return").unwrap(), vec![0x00, 0xE0, 0x00, 0xEE]);
+    }
+
+    #[test]
+    fn jumps_to_main_when_it_comes_later() {
+        let rom = assemble(": sprite 0xF0\n: main i := sprite").unwrap();
+        assert_eq!(rom, vec![0x12, 0x03, 0xF0, 0xA2, 0x02]);
+    }
+
+    #[test]
+    fn resolves_labels_used_before_they_are_defined() {
+        let rom = assemble(": main jump later :call later i := later : later ;").unwrap();
+        assert_eq!(rom, vec![0x12, 0x06, 0x22, 0x06, 0xA2, 0x06, 0x00, 0xEE]);
+
+        let rom = assemble(": main i := long data : data").unwrap();
+        assert_eq!(rom, vec![0xF0, 0x00, 0x02, 0x04]);
+
+        // A bare name calls the subroutine.
+        let rom = assemble(": main draw : draw ;").unwrap();
+        assert_eq!(rom, vec![0x22, 0x02, 0x00, 0xEE]);
+    }
+
+    #[test]
+    fn substitutes_constants() {
+        let rom = assemble(":const speed 3 :const target 0x300 \
+                            : main v0 := speed v1 += speed jump target").unwrap();
+        assert_eq!(rom, vec![0x60, 0x03, 0x71, 0x03, 0x13, 0x00]);
+    }
+
+    #[test]
+    fn substitutes_register_aliases() {
+        let rom = assemble(":alias x v5 :alias y vA : main x := 1 x += y").unwrap();
+        assert_eq!(rom, vec![0x65, 0x01, 0x85, 0xA4]);
+    }
+
+    #[test]
+    fn next_labels_the_second_byte_of_an_instruction() {
+        let rom = assemble(": main :next target v0 := 7 i := target").unwrap();
+        assert_eq!(rom, vec![0x60, 0x07, 0xA2, 0x01]);
+
+        let rom = assemble(": main i := target :next target v0 := 7").unwrap();
+        assert_eq!(rom, vec![0xA2, 0x03, 0x60, 0x07]);
+    }
+
+    #[test]
+    fn unpacks_addresses_into_v0_and_v1() {
+        let rom = assemble(": main :unpack 0xA data : data").unwrap();
+        assert_eq!(rom, vec![0x60, 0xA2, 0x61, 0x04]);
+
+        let rom = assemble(": data 0x55 : main :unpack 1 data").unwrap();
+        assert_eq!(rom, vec![0x12, 0x03, 0x55, 0x60, 0x12, 0x61, 0x02]);
+
+        let rom = assemble(": main :unpack long data : data").unwrap();
+        assert_eq!(rom, vec![0x60, 0x02, 0x61, 0x04]);
+    }
+
+    #[test]
+    fn if_then_skips_the_next_statement() {
+        assert_eq!(assemble(": main if v0 == 5 then v1 := 1").unwrap(),
+                   vec![0x40, 0x05, 0x61, 0x01]);
+        assert_eq!(assemble(": main if v0 != v1 then v1 := 1").unwrap(),
+                   vec![0x50, 0x10, 0x61, 0x01]);
+        assert_eq!(assemble(": main if v1 key then v0 := 0").unwrap(),
+                   vec![0xE1, 0xA1, 0x60, 0x00]);
+        assert_eq!(assemble(": main if v1 -key then v0 := 0").unwrap(),
+                   vec![0xE1, 0x9E, 0x60, 0x00]);
+
+        // Comparisons subtract in vf and test the borrow flag.
+        assert_eq!(assemble(": main if v1 < 3 then v0 := 0").unwrap(),
+                   vec![0x6F, 0x03, 0x8F, 0x17, 0x4F, 0x00, 0x60, 0x00]);
+        assert_eq!(assemble(": main if v1 > v2 then v0 := 0").unwrap(),
+                   vec![0x8F, 0x20, 0x8F, 0x15, 0x4F, 0x00, 0x60, 0x00]);
+        assert_eq!(assemble(": main if v1 <= v2 then v0 := 0").unwrap(),
+                   vec![0x8F, 0x20, 0x8F, 0x15, 0x3F, 0x00, 0x60, 0x00]);
+    }
+
+    #[test]
+    fn if_begin_jumps_over_blocks() {
+        assert_eq!(assemble(": main if v0 == 1 begin v1 := 1 end").unwrap(),
+                   vec![0x30, 0x01, 0x12, 0x06, 0x61, 0x01]);
+        assert_eq!(assemble(": main if v2 != v3 begin v4 := 2 else v4 := 3 end").unwrap(),
+                   vec![0x92, 0x30, 0x12, 0x08, 0x64, 0x02, 0x12, 0x0A, 0x64, 0x03]);
+    }
+
+    #[test]
+    fn while_leaves_the_innermost_loop() {
+        let rom = assemble(": main loop v0 += 1 while v0 != 10 again").unwrap();
+        assert_eq!(rom, vec![0x70, 0x01, 0x40, 0x0A, 0x12, 0x08, 0x12, 0x00]);
+
+        let rom = assemble(": main
+                              loop
+                                v0 += 1
+                                loop
+                                  v1 += 1
+                                  while v1 != 3
+                                again
+                                while v0 != 2
+                              again").unwrap();
+        assert_eq!(rom, vec![
+            0x70, 0x01, 0x71, 0x01, 0x41, 0x03, 0x12, 0x0A,
+            0x12, 0x02, 0x40, 0x02, 0x12, 0x10, 0x12, 0x00,
+        ]);
+    }
+
+    #[test]
+    fn places_raw_bytes() {
+        let rom = assemble(": main :byte 0xFF 0x12 -1 :org 0x208 :byte 7").unwrap();
+        assert_eq!(rom, vec![0xFF, 0x12, 0xFF, 0x00, 0x00, 0x00, 0x00, 0x00, 0x07]);
+    }
+
+    #[test]
+    fn compiles_schip_and_xo_chip_instructions() {
+        let rom = assemble(": main hires scroll-down 2 scroll-up 4 scroll-left scroll-right \
+                            save v1 - v3 load v3 - v1 exit").unwrap();
+        assert_eq!(rom, vec![
+            0x00, 0xFF, 0x00, 0xC2, 0x00, 0xD4, 0x00, 0xFC, 0x00, 0xFB,
+            0x51, 0x32, 0x53, 0x13, 0x00, 0xFD,
+        ]);
+    }
+
+    #[test]
+    fn refuses_instructions_notch_cannot_run() {
+        for source in &[": main plane 3", ": main audio", ": main pitch := v0",
+                        ": main i := bighex v0", ": main native 0x100"] {
+            let why = assemble(source).unwrap_err();
+            assert!(why.contains("not supported"), "{}: {}", source, why);
+        }
+    }
+
+    #[test]
+    fn reports_what_it_cannot_assemble() {
+        assert!(assemble(": start clear").unwrap_err().contains("main"));
+        assert!(assemble(": main\n  jump nowhere").unwrap_err().starts_with("line 2:"));
+        assert!(assemble(": main :macro twice { }").is_err());
+        assert!(assemble(": main loop").is_err());
+        assert!(assemble(": main again").is_err());
+        assert!(assemble(": main else").is_err());
+        assert!(assemble(": main while v0 == 1").is_err());
+        assert!(assemble(": main v0 := 256").is_err());
+        assert!(assemble(": main : main").is_err());
+    }
+}
@@ -0,0 +1,143 @@
+use std::fmt;
+
+// Number of colours in a palette. CHIP-8 only uses the first two, the extra
+// colours are for XO-CHIP's second bitplane and the overlap of both planes.
+pub const PALETTE_SIZE: usize = 4;
+
+// Built-in themes in the same order as a palette: background, foreground,
+// second bitplane and blended.
+const THEMES: [(&'static str, [u32; PALETTE_SIZE]); 5] = [
+    ("classic", [0x000000, 0xFFFFFF, 0xAAAAAA, 0x555555]),
+    ("green",   [0x0A1A0F, 0x33FF66, 0x1E9940, 0x145C28]),
+    ("amber",   [0x1A1000, 0xFFB000, 0xB37B00, 0x664600]),
+    ("lcd",     [0x9BBC0F, 0x0F380F, 0x306230, 0x8BAC0F]),
+    ("octo",    [0x996600, 0xFFCC00, 0xFF6600, 0x662200]),
+];
+
+/// A 24-bit colour.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+impl Rgb {
+    /// Creates a colour from a packed 0xRRGGBB value.
+    pub fn from_u32(value: u32) -> Rgb {
+        Rgb((value >> 16) as u8, (value >> 8) as u8, value as u8)
+    }
+
+    /// Mixes two colours, returning `self` when `amount` is 0 and `other` when
+    /// it is 1.
+    pub fn blend(self, other: Rgb, amount: f32) -> Rgb {
+        let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * amount).round() as u8;
+        Rgb(mix(self.0, other.0), mix(self.1, other.1), mix(self.2, other.2))
+    }
+
+    /// Parses a colour written as "RRGGBB" with an optional leading '#'.
+    pub fn parse(hex: &str) -> Result<Rgb, String> {
+        // Checking the digits up front also rejects the sign from_str_radix
+        // would accept, as in "+12345".
+        let digits = hex.trim().trim_left_matches('#');
+        if digits.len() != 6 || !digits.chars().all(|c| c.is_digit(16)) {
+            return Err(format!("invalid colour '{}', expected RRGGBB", hex));
+        }
+
+        match u32::from_str_radix(digits, 16) {
+            Ok(value) => Ok(Rgb::from_u32(value)),
+            Err(_) => Err(format!("invalid colour '{}', expected RRGGBB", hex)),
+        }
+    }
+}
+
+impl fmt::Display for Rgb {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#{:02X}{:02X}{:02X}", self.0, self.1, self.2)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Palette {
+    // Colours indexed by the value of a pixel on the display.
+    pub colors: [Rgb; PALETTE_SIZE],
+}
+
+impl Palette {
+    /// Looks up a built-in theme by name.
+    pub fn theme(name: &str) -> Option<Palette> {
+        THEMES.iter()
+            .find(|&&(theme, _)| theme == name)
+            .map(|&(_, colors)| Palette::from_u32(colors))
+    }
+
+    /// Returns the names of all built-in themes.
+    pub fn theme_names() -> Vec<&'static str> {
+        THEMES.iter().map(|&(name, _)| name).collect()
+    }
+
+    /// Parses a palette from either the name of a built-in theme or a comma
+    /// separated list of hex colours starting with the background. Colours
+    /// that are left out are taken from the classic theme.
+    pub fn parse(spec: &str) -> Result<Palette, String> {
+        if let Some(palette) = Palette::theme(spec.trim()) {
+            return Ok(palette);
+        }
+
+        let colors: Vec<&str> = spec.split(',').collect();
+        if colors.len() < 2 || colors.len() > PALETTE_SIZE {
+            return Err(format!("invalid palette '{}', expected a theme ({}) or 2 to {} colours",
+                               spec, Palette::theme_names().join(", "), PALETTE_SIZE));
+        }
+
+        let mut palette = Palette::default();
+        for (i, hex) in colors.iter().enumerate() {
+            palette.colors[i] = try!(Rgb::parse(hex));
+        }
+
+        Ok(palette)
+    }
+
+    /// The colour of pixels that are switched off.
+    pub fn background(&self) -> Rgb {
+        self.colors[0]
+    }
+
+    /// The colour of pixels that are switched on.
+    pub fn foreground(&self) -> Rgb {
+        self.colors[1]
+    }
+
+    fn from_u32(values: [u32; PALETTE_SIZE]) -> Palette {
+        let mut colors = [Rgb(0, 0, 0); PALETTE_SIZE];
+        for i in 0..PALETTE_SIZE {
+            colors[i] = Rgb::from_u32(values[i]);
+        }
+
+        Palette {
+            colors: colors,
+        }
+    }
+}
+
+impl Default for Palette {
+    /// White on black, like the original interpreter.
+    fn default() -> Palette {
+        Palette::from_u32(THEMES[0].1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Rgb;
+
+    #[test]
+    fn parse_accepts_six_hex_digits() {
+        assert_eq!(Rgb::parse("#FFCC00"), Ok(Rgb(0xFF, 0xCC, 0x00)));
+        assert_eq!(Rgb::parse("0a1a0f"), Ok(Rgb(0x0A, 0x1A, 0x0F)));
+    }
+
+    #[test]
+    fn parse_rejects_signs_and_other_characters() {
+        assert!(Rgb::parse("+12345").is_err());
+        assert!(Rgb::parse("-12345").is_err());
+        assert!(Rgb::parse("12345G").is_err());
+        assert!(Rgb::parse("12345").is_err());
+    }
+}
@@ -0,0 +1,94 @@
+use std::io::{self, Write};
+
+use super::byteorder::{BigEndian, WriteBytesExt};
+
+// Every PNG file starts with this signature.
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+
+// Largest amount of data a stored (uncompressed) deflate block can hold.
+const MAX_STORED_BLOCK: usize = 0xFFFF;
+
+// Color type for 8-bit truecolour images.
+const COLOR_TYPE_RGB: u8 = 2;
+
+/// Writes an 8-bit RGB image as a PNG. Screenshots of the display are tiny so
+/// the image data is stored without compression which keeps the encoder small.
+pub fn write_png<W: Write>(out: &mut W, width: usize, height: usize, rgb: &[u8]) -> io::Result<()> {
+    try!(out.write_all(&SIGNATURE));
+
+    // Image header.
+    let mut header = Vec::new();
+    try!(header.write_u32::<BigEndian>(width as u32));
+    try!(header.write_u32::<BigEndian>(height as u32));
+    try!(header.write_all(&[8, COLOR_TYPE_RGB, 0, 0, 0]));
+    try!(write_chunk(out, b"IHDR", &header));
+
+    // Every scanline is prefixed with the filter type, which is always none.
+    let stride = width * 3;
+    let mut raw = Vec::with_capacity((stride + 1) * height);
+    for row in rgb.chunks(stride).take(height) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+    try!(write_chunk(out, b"IDAT", &zlib_stored(&raw)));
+
+    write_chunk(out, b"IEND", &[])
+}
+
+/// Writes a chunk with its length and checksum.
+fn write_chunk<W: Write>(out: &mut W, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    try!(out.write_u32::<BigEndian>(data.len() as u32));
+    try!(out.write_all(kind));
+    try!(out.write_all(data));
+
+    let crc = crc32(crc32(0, kind), data);
+    try!(out.write_u32::<BigEndian>(crc));
+    Ok(())
+}
+
+/// Wraps data in a zlib stream made of stored deflate blocks.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01];
+
+    let blocks: Vec<&[u8]> = data.chunks(MAX_STORED_BLOCK).collect();
+    if blocks.is_empty() {
+        out.extend_from_slice(&[0x01, 0x00, 0x00, 0xFF, 0xFF]);
+    }
+    for (i, block) in blocks.iter().enumerate() {
+        let last = if i == blocks.len() - 1 { 1 } else { 0 };
+        let len = block.len() as u16;
+
+        out.push(last);
+        out.extend_from_slice(&[len as u8, (len >> 8) as u8]);
+        out.extend_from_slice(&[!len as u8, (!len >> 8) as u8]);
+        out.extend_from_slice(block);
+    }
+
+    let adler = adler32(data);
+    out.extend_from_slice(&[(adler >> 24) as u8, (adler >> 16) as u8,
+                            (adler >> 8) as u8, adler as u8]);
+    out
+}
+
+/// Continues a CRC-32 checksum (as used by PNG and zip) over more data.
+pub fn crc32(crc: u32, data: &[u8]) -> u32 {
+    let mut crc = !crc;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { 0xEDB88320 ^ (crc >> 1) } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+/// Computes the Adler-32 checksum used by zlib streams.
+fn adler32(data: &[u8]) -> u32 {
+    let mut a: u32 = 1;
+    let mut b: u32 = 0;
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
@@ -0,0 +1,162 @@
+use std::fmt;
+
+/// The family of CHIP-8 interpreters a rom was written for.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Platform {
+    // The original interpreter on the COSMAC VIP.
+    Chip8,
+
+    // SUPER-CHIP 1.1 on HP48 calculators.
+    SuperChip,
+
+    // Octo's XO-CHIP extensions.
+    XoChip,
+}
+
+impl Platform {
+    pub fn parse(name: &str) -> Result<Platform, String> {
+        match name.trim() {
+            "chip8" => Ok(Platform::Chip8),
+            "schip" => Ok(Platform::SuperChip),
+            "xochip" => Ok(Platform::XoChip),
+            _ => Err(format!("invalid platform '{}', expected chip8, schip or xochip", name)),
+        }
+    }
+
+    /// The name the platform is usually known by.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Platform::Chip8 => "CHIP-8",
+            Platform::SuperChip => "SCHIP",
+            Platform::XoChip => "XO-CHIP",
+        }
+    }
+
+    /// Bytes of memory available to programs, including the reserved space
+    /// below where they are loaded.
+    pub fn memory_size(&self) -> usize {
+        match *self {
+            Platform::Chip8 | Platform::SuperChip => 0x1000,
+            Platform::XoChip => 0x10000,
+        }
+    }
+
+    /// The quirks of the platform's reference interpreter.
+    pub fn quirks(&self) -> Quirks {
+        match *self {
+            Platform::Chip8 => Quirks {
+                shift: false,
+                load_store: true,
+                jump: false,
+                vf_reset: true,
+                clip: true,
+            },
+            Platform::SuperChip => Quirks {
+                shift: true,
+                load_store: false,
+                jump: true,
+                vf_reset: false,
+                clip: true,
+            },
+            Platform::XoChip => Quirks {
+                shift: false,
+                load_store: true,
+                jump: false,
+                vf_reset: false,
+                clip: false,
+            },
+        }
+    }
+}
+
+impl Default for Platform {
+    fn default() -> Platform {
+        Platform::Chip8
+    }
+}
+
+/// Behaviours that differ between interpreters and that roms rely on. The
+/// default is the behaviour notch has always had, which suits most games
+/// written for modern interpreters.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quirks {
+    // 8XY6 and 8XYE shift VX in place instead of shifting VY into VX.
+    pub shift: bool,
+
+    // FX55 and FX65 leave I pointing past the last register they copied.
+    pub load_store: bool,
+
+    // BNNN jumps to NNN plus VX, where X is the highest nibble of NNN,
+    // instead of plus V0.
+    pub jump: bool,
+
+    // 8XY1, 8XY2 and 8XY3 clear VF.
+    pub vf_reset: bool,
+
+    // Sprites are cut off at the edges of the display instead of wrapping
+    // around to the other side.
+    pub clip: bool,
+}
+
+impl Default for Quirks {
+    fn default() -> Quirks {
+        Quirks {
+            shift: true,
+            load_store: false,
+            jump: false,
+            vf_reset: false,
+            clip: false,
+        }
+    }
+}
+
+impl Quirks {
+    /// Parses a comma separated list of the quirks to enable, or "none".
+    ///
+    ///     shift, jump, clip
+    pub fn parse(list: &str) -> Result<Quirks, String> {
+        let mut quirks = Quirks {
+            shift: false,
+            load_store: false,
+            jump: false,
+            vf_reset: false,
+            clip: false,
+        };
+        if list.trim() == "none" {
+            return Ok(quirks);
+        }
+
+        for name in list.split(',').map(|name| name.trim()) {
+            match name {
+                "shift" => quirks.shift = true,
+                "load-store" => quirks.load_store = true,
+                "jump" => quirks.jump = true,
+                "vf-reset" => quirks.vf_reset = true,
+                "clip" => quirks.clip = true,
+                _ => return Err(format!("invalid quirk '{}', expected shift, load-store, jump, \
+                                         vf-reset, clip or none", name)),
+            }
+        }
+
+        Ok(quirks)
+    }
+}
+
+impl fmt::Display for Quirks {
+    /// Formats the quirks as a list `Quirks::parse` reads back.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let names: Vec<&str> = [
+            (self.shift, "shift"),
+            (self.load_store, "load-store"),
+            (self.jump, "jump"),
+            (self.vf_reset, "vf-reset"),
+            (self.clip, "clip"),
+        ].iter().filter(|&&(enabled, _)| enabled).map(|&(_, name)| name).collect();
+
+        if names.is_empty() {
+            write!(f, "none")
+        } else {
+            write!(f, "{}", names.join(", "))
+        }
+    }
+}
@@ -0,0 +1,328 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use super::display::Display;
+use super::gif::GifEncoder;
+use super::palette::{Palette, Rgb, PALETTE_SIZE};
+use super::sound::{Synth, Tone, SAMPLE_RATE};
+use super::wav::WavWriter;
+
+// The virtual machine runs at 60 frames per second.
+const FRAME_RATE: u64 = 60;
+
+// Browsers slow down GIF frames shorter than this many hundredths of a
+// second, so quicker changes are merged into the next frame.
+const MIN_GIF_DELAY: u64 = 2;
+
+enum Output {
+    // An animated GIF. Frames are only written when the display changes and
+    // are held for as long as the display stays the same.
+    Gif {
+        encoder: GifEncoder<BufWriter<File>>,
+
+        // The frame waiting to be written until the display changes.
+        pending: Option<Vec<u8>>,
+
+        // Hundredths of a second written to the file so far.
+        written: u64,
+    },
+
+    // A raw YUV4MPEG2 stream with every frame written out. The palette is
+    // converted to YUV ahead of time.
+    Y4m {
+        out: BufWriter<File>,
+        yuv: [(u8, u8, u8); PALETTE_SIZE],
+    },
+}
+
+/// Records the display to a file once per frame. Recordings follow the
+/// emulated frames rather than the wall clock, so they play back smoothly even
+/// when the host could not keep up.
+pub struct Recorder {
+    path: PathBuf,
+    output: Output,
+
+    // Size of the recording, which stays fixed even if the resolution of
+    // the display changes.
+    width: usize,
+    height: usize,
+
+    // Number of frames recorded.
+    frames: u64,
+
+    finished: bool,
+}
+
+impl Recorder {
+    /// Starts a recording in a format picked by the file extension, either
+    /// "gif" or "y4m". The recording is `scale` times larger than the display.
+    pub fn create<P: AsRef<Path>>(path: P, display: &Display, palette: &Palette, scale: usize) -> io::Result<Recorder> {
+        let path = path.as_ref().to_path_buf();
+        let width = display.width() * scale;
+        let height = display.height() * scale;
+
+        let extension = path.extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_lowercase());
+
+        let output = match extension.as_ref().map(|extension| extension.as_str()) {
+            Some("gif") => {
+                let out = BufWriter::new(try!(File::create(&path)));
+                Output::Gif {
+                    encoder: try!(GifEncoder::new(out, width, height, palette)),
+                    pending: None,
+                    written: 0,
+                }
+            },
+            Some("y4m") => {
+                let mut out = BufWriter::new(try!(File::create(&path)));
+                try!(write!(out, "YUV4MPEG2 W{} H{} F{}:1 Ip A1:1 C444\n", width, height, FRAME_RATE));
+
+                let mut yuv = [(0, 0, 0); PALETTE_SIZE];
+                for i in 0..PALETTE_SIZE {
+                    yuv[i] = to_yuv(palette.colors[i]);
+                }
+                Output::Y4m { out: out, yuv: yuv }
+            },
+            _ => {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                          "unknown recording format, expected .gif or .y4m"));
+            },
+        };
+
+        Ok(Recorder {
+            path: path,
+            output: output,
+            width: width,
+            height: height,
+            frames: 0,
+            finished: false,
+        })
+    }
+
+    /// Adds the display as it is at the end of a frame to the recording.
+    pub fn add_frame(&mut self, display: &Display) -> io::Result<()> {
+        let indices = sample(display, self.width, self.height);
+        let frame = self.frames;
+        self.frames += 1;
+
+        match self.output {
+            Output::Gif { ref mut encoder, ref mut pending, ref mut written } => {
+                if pending.as_ref() == Some(&indices) {
+                    return Ok(());
+                }
+
+                // Write out the previous frame once it has been shown long
+                // enough, otherwise the new frame replaces it and takes over
+                // its time.
+                if let Some(ref previous) = *pending {
+                    let delay = centiseconds(frame) - *written;
+                    if delay >= MIN_GIF_DELAY {
+                        try!(write_gif_frame(encoder, previous, delay));
+                        *written += delay;
+                    }
+                }
+                *pending = Some(indices);
+            },
+            Output::Y4m { ref mut out, ref yuv } => {
+                try!(out.write_all(b"FRAME\n"));
+                for plane in 0..3 {
+                    let bytes: Vec<u8> = indices.iter().map(|&index| {
+                        let (y, u, v) = yuv[index as usize];
+                        match plane { 0 => y, 1 => u, _ => v }
+                    }).collect();
+                    try!(out.write_all(&bytes));
+                }
+            },
+        }
+
+        Ok(())
+    }
+
+    /// Writes any remaining frames and completes the file.
+    pub fn finish(&mut self) -> io::Result<()> {
+        if self.finished {
+            return Ok(());
+        }
+        self.finished = true;
+
+        let frames = self.frames;
+        match self.output {
+            Output::Gif { ref mut encoder, ref mut pending, ref mut written } => {
+                if let Some(indices) = pending.take() {
+                    let delay = centiseconds(frames).saturating_sub(*written);
+                    try!(write_gif_frame(encoder, &indices, delay.max(MIN_GIF_DELAY)));
+                }
+                encoder.finish()
+            },
+            Output::Y4m { ref mut out, .. } => out.flush(),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        if let Err(why) = self.finish() {
+            println!("notch: cannot finish recording '{}': {}", self.path.display(), why);
+        }
+    }
+}
+
+/// Records the beep to a WAV file once per frame. The beep is generated by the
+/// same synthesizer the audio device plays, but clocked by emulated frames so
+/// the recording works without an audio device and never drifts.
+pub struct AudioRecorder {
+    path: PathBuf,
+    writer: WavWriter<BufWriter<File>>,
+    synth: Synth,
+    finished: bool,
+}
+
+impl AudioRecorder {
+    pub fn create<P: AsRef<Path>>(path: P, tone: Tone) -> io::Result<AudioRecorder> {
+        let path = path.as_ref().to_path_buf();
+        let out = BufWriter::new(try!(File::create(&path)));
+
+        Ok(AudioRecorder {
+            writer: try!(WavWriter::new(out, SAMPLE_RATE as u32)),
+            synth: Synth::new(tone, SAMPLE_RATE),
+            path: path,
+            finished: false,
+        })
+    }
+
+    /// Writes the audio of a frame, then starts or stops the beep for the
+    /// next frame.
+    pub fn add_frame(&mut self, beeping: bool) -> io::Result<()> {
+        let length = SAMPLE_RATE as usize / FRAME_RATE as usize;
+        let samples: Vec<f32> = (0..length).map(|_| self.synth.sample()).collect();
+        try!(self.writer.write_samples(&samples));
+
+        self.synth.beeping = beeping;
+        Ok(())
+    }
+
+    /// Writes silence instead of the beep while muted, the same as the
+    /// speakers play.
+    pub fn set_muted(&mut self, muted: bool) {
+        self.synth.muted = muted;
+    }
+
+    pub fn finish(&mut self) -> io::Result<()> {
+        if self.finished {
+            return Ok(());
+        }
+        self.finished = true;
+        self.writer.finish()
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for AudioRecorder {
+    fn drop(&mut self) {
+        if let Err(why) = self.finish() {
+            println!("notch: cannot finish recording '{}': {}", self.path.display(), why);
+        }
+    }
+}
+
+/// Writes a frame shown for a number of hundredths of a second. GIF delays
+/// are 16-bit, so a frame shown for longer than about eleven minutes is
+/// repeated, each copy taking a share of the time.
+fn write_gif_frame<W: Write>(encoder: &mut GifEncoder<W>, indices: &[u8], delay: u64)
+                             -> io::Result<()> {
+    let max_delay = u16::max_value() as u64;
+    let mut remaining = delay;
+
+    while remaining > max_delay {
+        // Leave the last copy at least the shortest delay viewers respect.
+        let part = if remaining - max_delay < MIN_GIF_DELAY {
+            max_delay - MIN_GIF_DELAY
+        } else {
+            max_delay
+        };
+        try!(encoder.write_frame(indices, part as u16));
+        remaining -= part;
+    }
+
+    encoder.write_frame(indices, remaining as u16)
+}
+
+/// Converts a number of frames into hundredths of a second.
+fn centiseconds(frames: u64) -> u64 {
+    frames * 100 / FRAME_RATE
+}
+
+/// Samples the display into a buffer of palette indices of the given size
+/// using nearest neighbour scaling.
+fn sample(display: &Display, width: usize, height: usize) -> Vec<u8> {
+    let pixels = display.pixels();
+    let mut indices = Vec::with_capacity(width * height);
+
+    for y in 0..height {
+        let offset = y * display.height() / height * display.width();
+        for x in 0..width {
+            indices.push(pixels[offset + x * display.width() / width]);
+        }
+    }
+
+    indices
+}
+
+/// Converts a colour to YUV using the BT.601 coefficients most encoders
+/// expect.
+fn to_yuv(rgb: Rgb) -> (u8, u8, u8) {
+    let (r, g, b) = (rgb.0 as f32, rgb.1 as f32, rgb.2 as f32);
+    let y = 16.0 + (65.738 * r + 129.057 * g + 25.064 * b) / 256.0;
+    let u = 128.0 + (-37.945 * r - 74.494 * g + 112.439 * b) / 256.0;
+    let v = 128.0 + (112.439 * r - 94.154 * g - 18.285 * b) / 256.0;
+
+    (y.round() as u8, u.round() as u8, v.round() as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{write_gif_frame, MIN_GIF_DELAY};
+    use super::super::gif::GifEncoder;
+    use super::super::palette::Palette;
+
+    /// Reads the delay of every graphic control extension in a GIF.
+    fn delays(gif: &[u8]) -> Vec<u64> {
+        gif.windows(6)
+            .filter(|window| window[..3] == [0x21, 0xF9, 0x04])
+            .map(|window| window[4] as u64 | (window[5] as u64) << 8)
+            .collect()
+    }
+
+    fn record(delay: u64) -> Vec<u64> {
+        let mut gif = Vec::new();
+        {
+            let mut encoder = GifEncoder::new(&mut gif, 2, 2, &Palette::default()).unwrap();
+            write_gif_frame(&mut encoder, &[0, 1, 1, 0], delay).unwrap();
+            encoder.finish().unwrap();
+        }
+        delays(&gif)
+    }
+
+    #[test]
+    fn short_delays_are_written_as_one_frame() {
+        assert_eq!(record(500), vec![500]);
+        assert_eq!(record(65535), vec![65535]);
+    }
+
+    #[test]
+    fn long_delays_are_split_without_wrapping() {
+        let delay = 65535 * 2 + 1;
+        let parts = record(delay);
+        assert_eq!(parts.iter().sum::<u64>(), delay);
+        assert!(parts.iter().all(|&part| part >= MIN_GIF_DELAY && part <= 65535));
+    }
+}
@@ -0,0 +1,141 @@
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+use super::cartridge::Cartridge;
+use super::gif;
+use super::zip::{self, ZipArchive};
+
+// Files in an archive with these extensions are listed as roms.
+const ROM_EXTENSIONS: [&'static str; 5] = ["ch8", "c8", "sc8", "xo8", "gif"];
+
+/// A rom ready to be loaded into memory.
+pub struct Rom {
+    pub program: Vec<u8>,
+
+    // Settings that came with the rom, such as those saved in an Octo
+    // cartridge, as keys of the config file.
+    pub options: Vec<(String, String)>,
+}
+
+/// What a file named on the command line holds: a rom, or a zip archive
+/// named without a file inside it, described by the roms it contains.
+pub enum Contents {
+    Rom(Rom),
+    Archive(String),
+}
+
+/// Splits a rom path into the file to read and the file to extract from it
+/// when the file is a zip archive, as in "pack.zip:games/tetris.ch8".
+pub fn split_path(path: &str) -> (&str, Option<&str>) {
+    match path.to_ascii_lowercase().find(".zip:") {
+        Some(pos) => (&path[..pos + 4], Some(&path[pos + 5..])),
+        None => (path, None),
+    }
+}
+
+/// The file name of a rom without its directory or archive.
+pub fn file_name(path: &str) -> Option<&str> {
+    let (file, member) = split_path(path);
+    Path::new(member.unwrap_or(file)).file_name().and_then(|name| name.to_str())
+}
+
+/// Reads a rom like `read`, failing with the list of roms when given an
+/// archive.
+pub fn read_bin(path: &str) -> Result<Rom, String> {
+    match try!(read(path)) {
+        Contents::Rom(rom) => Ok(rom),
+        Contents::Archive(listing) => Err(listing),
+    }
+}
+
+/// Reads a rom from a file, from a file inside a zip archive, or from
+/// standard input if the path is "-". Octo cartridge GIFs are unpacked into
+/// their program and the settings saved with it. Naming an archive without a
+/// file inside it gives a list of the roms it contains.
+pub fn read(path: &str) -> Result<Contents, String> {
+    let (file, member) = split_path(path);
+    let mut buffer: Vec<u8> = Vec::new();
+
+    let result = if file == "-" {
+        io::stdin().read_to_end(&mut buffer)
+    } else {
+        match File::open(file) {
+            Ok(ref mut file) => file.read_to_end(&mut buffer),
+            Err(why) => return Err(format!("cannot open '{}': {}", file, why)),
+        }
+    };
+    if let Err(why) = result {
+        return Err(format!("cannot read '{}': {}", file, why));
+    }
+
+    if zip::is_zip(&buffer) {
+        let archive = try!(ZipArchive::parse(buffer).map_err(|why| format!("'{}': {}", file, why)));
+        buffer = match member {
+            Some(member) => try!(archive.read(member).map_err(|why| format!("'{}': {}", file, why))),
+            None => return Ok(Contents::Archive(list_roms(file, &archive))),
+        };
+    } else if member.is_some() {
+        return Err(format!("'{}' is not a zip archive", file));
+    }
+
+    if gif::is_gif(&buffer) {
+        let cartridge = try!(Cartridge::decode(&buffer)
+            .map_err(|why| format!("cannot load '{}': {}", path, why)));
+        return Ok(Contents::Rom(Rom {
+            program: cartridge.program,
+            options: cartridge.options,
+        }));
+    }
+
+    Ok(Contents::Rom(Rom {
+        program: buffer,
+        options: Vec::new(),
+    }))
+}
+
+/// Describes how to pick a rom from an archive. Every file is listed when
+/// none of them look like roms.
+fn list_roms(file: &str, archive: &ZipArchive) -> String {
+    let names = archive.names();
+    let roms: Vec<&str> = names.iter()
+        .cloned()
+        .filter(|name| {
+            let extension = Path::new(name).extension().and_then(|extension| extension.to_str());
+            extension.map_or(false, |extension| {
+                ROM_EXTENSIONS.contains(&extension.to_lowercase().as_str())
+            })
+        })
+        .collect();
+    let roms = if roms.is_empty() { names } else { roms };
+
+    let mut message = format!("'{}' is a zip archive, choose a rom with {}:NAME from:", file, file);
+    for name in roms {
+        message.push_str("\n    ");
+        message.push_str(name);
+    }
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{list_roms, split_path};
+    use super::super::zip::ZipArchive;
+
+    #[test]
+    fn splits_paths_into_archives() {
+        assert_eq!(split_path("pack.zip:games/tetris.ch8"), ("pack.zip", Some("games/tetris.ch8")));
+        assert_eq!(split_path("Pack.ZIP:a.ch8"), ("Pack.ZIP", Some("a.ch8")));
+        assert_eq!(split_path("tetris.ch8"), ("tetris.ch8", None));
+    }
+
+    #[test]
+    fn lists_only_the_roms_of_an_archive() {
+        let archive = ZipArchive::parse(include_bytes!("../tests/fixtures/roms.zip").to_vec()).unwrap();
+        let listing = list_roms("roms.zip", &archive);
+        assert!(listing.starts_with("'roms.zip' is a zip archive"));
+        assert!(listing.contains("\n    games/maze.ch8"));
+        assert!(listing.contains("\n    games/text.ch8"));
+        assert!(!listing.contains("readme.txt"));
+    }
+}
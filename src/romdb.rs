@@ -0,0 +1,296 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use super::config::{self, Config};
+use super::font::Font;
+use super::json::Json;
+use super::palette::{Palette, PALETTE_SIZE};
+use super::quirks::{Platform, Quirks};
+use super::sha1::sha1_hex;
+
+// Database of known roms built into the binary.
+const BUNDLED_DATABASE: &'static str = include_str!("../data/roms.ini");
+
+/// Settings for known roms, such as their platform and quirks, keyed by the
+/// SHA-1 of the rom. The database uses the same format as the configuration
+/// file with one section per rom. Entries in `roms.ini` in the configuration
+/// directory override the bundled ones key by key. The community CHIP-8
+/// database's `programs.json` is read from the configuration directory as
+/// well, ranking between the two.
+pub struct RomDatabase {
+    // Databases in order of precedence, user entries first.
+    databases: Vec<Config>,
+}
+
+impl RomDatabase {
+    /// Loads the bundled database and the user's database if there is one.
+    pub fn load() -> Result<RomDatabase, String> {
+        let mut databases = Vec::new();
+
+        if let Some(path) = RomDatabase::user_path() {
+            if path.exists() {
+                databases.push(try!(Config::load(path)));
+            }
+        }
+        if let Some(path) = config::config_dir().map(|dir| dir.join("programs.json")) {
+            if path.exists() {
+                databases.push(try!(load_programs(&path)));
+            }
+        }
+
+        let bundled = try!(Config::parse(BUNDLED_DATABASE)
+            .map_err(|why| format!("bundled rom database: {}", why)));
+        databases.push(bundled);
+
+        Ok(RomDatabase { databases: databases })
+    }
+
+    /// Location of the user's rom database.
+    pub fn user_path() -> Option<PathBuf> {
+        config::config_dir().map(|dir| dir.join("roms.ini"))
+    }
+
+    /// Looks up the settings for a rom. Roms that are not in the database
+    /// have no settings.
+    pub fn lookup(&self, rom: &[u8]) -> RomEntry {
+        let sha1 = sha1_hex(rom);
+        let mut values = HashMap::new();
+
+        for database in self.databases.iter().rev() {
+            for (key, value) in database.entries(&sha1) {
+                values.insert(key.to_string(), value.to_string());
+            }
+        }
+
+        RomEntry {
+            sha1: sha1,
+            values: values,
+        }
+    }
+}
+
+/// Reads the CHIP-8 database's list of programs.
+fn load_programs(path: &Path) -> Result<Config, String> {
+    let mut contents = String::new();
+    match File::open(path) {
+        Ok(ref mut file) => {
+            if let Err(why) = file.read_to_string(&mut contents) {
+                return Err(format!("cannot read '{}': {}", path.display(), why));
+            }
+        },
+        Err(why) => return Err(format!("cannot open '{}': {}", path.display(), why)),
+    }
+
+    let programs = try!(Json::parse(&contents)
+        .map_err(|why| format!("'{}': {}", path.display(), why)));
+    Ok(import_programs(&programs))
+}
+
+/// Translates the CHIP-8 database's programs into rom database sections.
+/// Each program lists its roms by SHA-1 along with the platforms they run on,
+/// in order of preference, and how they differ from those platforms.
+fn import_programs(programs: &Json) -> Config {
+    let mut database = Config::default();
+    let programs = match *programs {
+        Json::Array(ref programs) => programs,
+        _ => return database,
+    };
+
+    for program in programs.iter() {
+        let roms = match program.get("roms") {
+            Some(&Json::Object(ref roms)) => roms,
+            _ => continue,
+        };
+
+        for (sha1, rom) in roms.iter() {
+            let sha1 = sha1.to_lowercase();
+            if let Some(&Json::String(ref title)) = program.get("title") {
+                database.set(&sha1, "title", title);
+            }
+            for (key, value) in import_rom(rom) {
+                database.set(&sha1, key, &value);
+            }
+        }
+    }
+
+    database
+}
+
+/// Translates the settings of one rom of the CHIP-8 database.
+fn import_rom(rom: &Json) -> Vec<(&'static str, String)> {
+    let mut settings = Vec::new();
+
+    // The first platform notch can emulate is used.
+    let platform = match rom.get("platforms") {
+        Some(&Json::Array(ref platforms)) => platforms.iter().filter_map(|platform| match *platform {
+            Json::String(ref id) => import_platform(id).map(|quirks| (id.clone(), quirks)),
+            _ => None,
+        }).next(),
+        _ => None,
+    };
+
+    if let Some((id, (platform, mut quirks))) = platform {
+        // Quirks the rom needs beyond those of the platform.
+        if let Some(overrides) = rom.get("quirkyPlatforms").and_then(|quirky| quirky.get(&id)) {
+            let quirk = |name: &str| match overrides.get(name) {
+                Some(&Json::Bool(value)) => Some(value),
+                _ => None,
+            };
+            if let Some(shift) = quirk("shift") {
+                quirks.shift = shift;
+            }
+            if let Some(unchanged) = quirk("memoryLeaveIUnchanged") {
+                quirks.load_store = !unchanged;
+            }
+            if let Some(jump) = quirk("jump") {
+                quirks.jump = jump;
+            }
+            if let Some(logic) = quirk("logic") {
+                quirks.vf_reset = logic;
+            }
+            if let Some(wrap) = quirk("wrap") {
+                quirks.clip = !wrap;
+            }
+        }
+
+        let name = match platform {
+            Platform::Chip8 => "chip8",
+            Platform::SuperChip => "schip",
+            Platform::XoChip => "xochip",
+        };
+        settings.push(("platform", name.to_string()));
+        settings.push(("quirks", quirks.to_string()));
+    }
+
+    if let Some(&Json::Number(tickrate)) = rom.get("tickrate") {
+        if tickrate >= 1.0 {
+            settings.push(("instructions-per-frame", format!("{}", tickrate as u64)));
+        }
+    }
+    if let Some(&Json::Number(address)) = rom.get("startAddress") {
+        settings.push(("load-address", format!("{:#x}", address as u64)));
+    }
+    if let Some(&Json::String(ref font)) = rom.get("fontStyle") {
+        if Font::builtin(font).is_some() {
+            settings.push(("font", font.clone()));
+        }
+    }
+
+    // Colours are given background first, like notch's palettes. Colours
+    // notch cannot read are left out rather than refusing to run the rom.
+    if let Some(&Json::Array(ref pixels)) = rom.get("colors").and_then(|colors| colors.get("pixels")) {
+        let colors: Vec<&str> = pixels.iter()
+            .filter_map(|color| match *color {
+                Json::String(ref color) => Some(color.as_str()),
+                _ => None,
+            })
+            .collect();
+        let palette = colors[..colors.len().min(PALETTE_SIZE)].join(",");
+        if Palette::parse(&palette).is_ok() {
+            settings.push(("palette", palette));
+        }
+    }
+
+    settings
+}
+
+/// Maps a platform of the CHIP-8 database to the notch platform and quirks
+/// closest to it. Platforms notch cannot emulate give None.
+fn import_platform(id: &str) -> Option<(Platform, Quirks)> {
+    let platform = match id {
+        "originalChip8" | "hybridVIP" | "modernChip8" | "chip8x" => Platform::Chip8,
+        "chip48" | "superchip1" | "superchip" => Platform::SuperChip,
+        "xochip" => Platform::XoChip,
+        _ => return None,
+    };
+
+    // Modern CHIP-8 interpreters dropped the VIP's VF reset.
+    let mut quirks = platform.quirks();
+    if id == "modernChip8" {
+        quirks.vf_reset = false;
+    }
+    Some((platform, quirks))
+}
+
+/// Settings for a single rom from the database.
+#[derive(Clone, Debug, Default)]
+pub struct RomEntry {
+    pub sha1: String,
+    values: HashMap<String, String>,
+}
+
+impl RomEntry {
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(|value| value.as_str())
+    }
+
+    pub fn title(&self) -> Option<&str> {
+        self.get("title")
+    }
+
+    /// Sets a key unless the database already has a value for it.
+    pub fn set_default(&mut self, key: &str, value: &str) {
+        if !self.values.contains_key(key) {
+            self.values.insert(key.to_string(), value.to_string());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::import_programs;
+    use super::super::json::Json;
+
+    // A trimmed down entry in the format of the CHIP-8 database.
+    const PROGRAMS: &'static str = r##"[
+        {
+            "title": "Example",
+            "roms": {
+                "0123456789ABCDEF0123456789ABCDEF01234567": {
+                    "file": "example.ch8",
+                    "platforms": ["megachip8", "superchip", "xochip"],
+                    "quirkyPlatforms": {
+                        "superchip": { "memoryLeaveIUnchanged": false, "wrap": true }
+                    },
+                    "tickrate": 30,
+                    "startAddress": 512,
+                    "fontStyle": "octo",
+                    "colors": { "pixels": ["#000000", "#ff0000"] }
+                },
+                "89abcdef0123456789abcdef0123456789abcdef": {
+                    "platforms": ["modernChip8"],
+                    "colors": { "pixels": ["#000", "#fff"] }
+                }
+            }
+        },
+        { "title": "No roms" }
+    ]"##;
+
+    #[test]
+    fn imports_settings_of_the_first_supported_platform() {
+        let database = import_programs(&Json::parse(PROGRAMS).unwrap());
+        let section = "0123456789abcdef0123456789abcdef01234567";
+
+        assert_eq!(database.get(section, "title"), Some("Example"));
+        assert_eq!(database.get(section, "platform"), Some("schip"));
+        assert_eq!(database.get(section, "quirks"), Some("shift, load-store, jump"));
+        assert_eq!(database.get(section, "instructions-per-frame"), Some("30"));
+        assert_eq!(database.get(section, "load-address"), Some("0x200"));
+        assert_eq!(database.get(section, "palette"), Some("#000000,#ff0000"));
+
+        // Fonts notch does not have are left out.
+        assert_eq!(database.get(section, "font"), None);
+    }
+
+    #[test]
+    fn leaves_out_what_notch_cannot_read() {
+        let database = import_programs(&Json::parse(PROGRAMS).unwrap());
+        let section = "89abcdef0123456789abcdef0123456789abcdef";
+
+        assert_eq!(database.get(section, "platform"), Some("chip8"));
+        assert_eq!(database.get(section, "quirks"), Some("load-store, clip"));
+        assert_eq!(database.get(section, "palette"), None);
+    }
+}
@@ -0,0 +1,36 @@
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+use super::display::Display;
+use super::palette::{Palette, Rgb};
+use super::png::write_png;
+
+/// Renders the display as 8-bit RGB using a palette, with every pixel scaled
+/// up to a square of `scale` pixels.
+pub fn render_rgb(display: &Display, palette: &Palette, scale: usize) -> Vec<u8> {
+    let width = display.width() * scale;
+    let height = display.height() * scale;
+    let pixels = display.pixels();
+    let mut rgb = Vec::with_capacity(width * height * 3);
+
+    for y in 0..height {
+        let offset = (y / scale) * display.width();
+        for x in 0..width {
+            let Rgb(r, g, b) = palette.colors[pixels[offset + x / scale] as usize];
+            rgb.push(r);
+            rgb.push(g);
+            rgb.push(b);
+        }
+    }
+
+    rgb
+}
+
+/// Saves a pixel exact image of the display as a PNG file.
+pub fn save_png<P: AsRef<Path>>(path: P, display: &Display, palette: &Palette, scale: usize) -> io::Result<()> {
+    let rgb = render_rgb(display, palette, scale);
+    let mut file = try!(File::create(path));
+
+    write_png(&mut file, display.width() * scale, display.height() * scale, &rgb)
+}
@@ -0,0 +1,264 @@
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+use super::frontend::HostEvent;
+
+/// Something a script does once its statement is reached.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Action {
+    Press(u8),
+    Release(u8),
+
+    // Hold back the rest of the script until the program counter reaches an
+    // address.
+    WaitForPc(u16),
+
+    Quit,
+}
+
+#[derive(Clone, Debug)]
+struct Statement {
+    // The statement runs no earlier than the start of this frame.
+    frame: Option<u64>,
+    action: Action,
+}
+
+/// Keypad input played back from a script for automated playthroughs.
+/// Statements are separated by semicolons or new lines and run in order, each
+/// one waiting for the one before it. Lines starting with `#` are comments.
+///
+///     frame 120: press 5; frame 130: release 5
+///     wait until pc=0x2a4
+///     press a; frame 300: quit
+#[derive(Clone, Debug)]
+pub struct Script {
+    statements: Vec<Statement>,
+
+    // Index of the next statement to run.
+    next: usize,
+}
+
+impl Script {
+    /// Reads and parses a script file, or standard input if the path is "-".
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Script, String> {
+        let filename = format!("{}", path.as_ref().display());
+        let mut contents = String::new();
+
+        let result = if filename == "-" {
+            io::stdin().read_to_string(&mut contents)
+        } else {
+            match File::open(path) {
+                Ok(ref mut file) => file.read_to_string(&mut contents),
+                Err(why) => return Err(format!("cannot open '{}': {}", filename, why)),
+            }
+        };
+        if let Err(why) = result {
+            return Err(format!("cannot read '{}': {}", filename, why));
+        }
+
+        Script::parse(&contents).map_err(|why| format!("{}: {}", filename, why))
+    }
+
+    pub fn parse(contents: &str) -> Result<Script, String> {
+        let mut statements = Vec::new();
+
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.starts_with('#') {
+                continue;
+            }
+
+            for statement in line.split(';').map(|statement| statement.trim()) {
+                if statement.is_empty() {
+                    continue;
+                }
+                match parse_statement(statement) {
+                    Ok(statement) => statements.push(statement),
+                    Err(why) => return Err(format!("line {}: {}", number + 1, why)),
+                }
+            }
+        }
+
+        Ok(Script {
+            statements: statements,
+            next: 0,
+        })
+    }
+
+    /// Returns true if the script ends the run once it gets far enough.
+    pub fn quits(&self) -> bool {
+        self.statements.iter().any(|statement| statement.action == Action::Quit)
+    }
+
+    /// Runs the statements that are due at the given frame and program
+    /// counter and returns the input they produce. This is called after every
+    /// instruction.
+    pub fn poll(&mut self, frame: u64, pc: u16) -> Vec<HostEvent> {
+        let mut events = Vec::new();
+
+        while let Some(statement) = self.statements.get(self.next) {
+            if statement.frame.map_or(false, |at| frame < at) {
+                break;
+            }
+
+            match statement.action {
+                Action::Press(key) => events.push(HostEvent::Key(key, true)),
+                Action::Release(key) => events.push(HostEvent::Key(key, false)),
+                Action::WaitForPc(address) if address != pc => break,
+                Action::WaitForPc(_) => {},
+                Action::Quit => events.push(HostEvent::Quit),
+            }
+            self.next += 1;
+        }
+
+        events
+    }
+}
+
+/// Parses a statement with an optional "frame N:" prefix.
+fn parse_statement(statement: &str) -> Result<Statement, String> {
+    let (frame, action) = if statement.starts_with("frame ") {
+        match statement.find(':') {
+            Some(pos) => {
+                let number = statement["frame ".len()..pos].trim();
+                match number.parse::<u64>() {
+                    Ok(frame) => (Some(frame), statement[pos + 1..].trim()),
+                    Err(_) => return Err(format!("invalid frame '{}'", number)),
+                }
+            },
+            None => return Err(format!("expected ':' after the frame in '{}'", statement)),
+        }
+    } else {
+        (None, statement)
+    };
+
+    Ok(Statement {
+        frame: frame,
+        action: try!(parse_action(action)),
+    })
+}
+
+fn parse_action(action: &str) -> Result<Action, String> {
+    let words: Vec<&str> = action.split_whitespace().collect();
+
+    match words.first().cloned() {
+        Some("press") if words.len() == 2 => Ok(Action::Press(try!(parse_key(words[1])))),
+        Some("release") if words.len() == 2 => Ok(Action::Release(try!(parse_key(words[1])))),
+        Some("quit") if words.len() == 1 => Ok(Action::Quit),
+        Some("wait") if action.starts_with("wait until ") => {
+            let condition: String = action["wait until ".len()..].split_whitespace().collect();
+            if !condition.starts_with("pc=") {
+                return Err(format!("unknown condition '{}', expected pc=ADDRESS", condition));
+            }
+            Ok(Action::WaitForPc(try!(parse_address(&condition["pc=".len()..]))))
+        },
+        _ => Err(format!("unknown statement '{}'", action)),
+    }
+}
+
+/// Parses a keypad key written as a hex digit.
+fn parse_key(key: &str) -> Result<u8, String> {
+    match u8::from_str_radix(key, 16) {
+        Ok(value) if key.len() == 1 => Ok(value),
+        _ => Err(format!("invalid key '{}', expected 0 to F", key)),
+    }
+}
+
+/// Parses an address written in hex with a 0x prefix or in decimal.
+fn parse_address(address: &str) -> Result<u16, String> {
+    let result = if address.starts_with("0x") || address.starts_with("0X") {
+        u16::from_str_radix(&address[2..], 16)
+    } else {
+        address.parse::<u16>()
+    };
+
+    result.map_err(|_| format!("invalid address '{}'", address))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Action, Script, Statement};
+    use super::super::frontend::HostEvent;
+
+    fn actions(script: &Script) -> Vec<(Option<u64>, Action)> {
+        script.statements.iter()
+            .map(|&Statement { frame, action }| (frame, action))
+            .collect()
+    }
+
+    #[test]
+    fn parse_splits_statements_on_lines_and_semicolons() {
+        let script = Script::parse("# comment\npress 5; release 5\n\n  quit  ").unwrap();
+        assert_eq!(actions(&script), vec![
+            (None, Action::Press(5)),
+            (None, Action::Release(5)),
+            (None, Action::Quit),
+        ]);
+    }
+
+    #[test]
+    fn parse_reads_keys_as_hex_digits() {
+        let script = Script::parse("press a; press F; press 0").unwrap();
+        assert_eq!(actions(&script), vec![
+            (None, Action::Press(0xA)),
+            (None, Action::Press(0xF)),
+            (None, Action::Press(0x0)),
+        ]);
+        assert!(Script::parse("press g").is_err());
+        assert!(Script::parse("press 10").is_err());
+        assert!(Script::parse("press").is_err());
+    }
+
+    #[test]
+    fn parse_reads_frame_prefixes() {
+        let script = Script::parse("frame 120: press 5;frame 130:release 5").unwrap();
+        assert_eq!(actions(&script), vec![
+            (Some(120), Action::Press(5)),
+            (Some(130), Action::Release(5)),
+        ]);
+        assert!(Script::parse("frame x: quit").is_err());
+        assert!(Script::parse("frame 10 quit").is_err());
+    }
+
+    #[test]
+    fn parse_reads_pc_conditions_in_hex_and_decimal() {
+        let script = Script::parse("wait until pc=0x2a4\nwait until pc = 512").unwrap();
+        assert_eq!(actions(&script), vec![
+            (None, Action::WaitForPc(0x2A4)),
+            (None, Action::WaitForPc(512)),
+        ]);
+        assert!(Script::parse("wait until i=0x200").is_err());
+        assert!(Script::parse("wait until pc=0x10000").is_err());
+    }
+
+    #[test]
+    fn parse_reports_the_line_of_an_error() {
+        let why = Script::parse("press 1\n\njump 5").unwrap_err();
+        assert!(why.starts_with("line 3:"), "{}", why);
+    }
+
+    #[test]
+    fn poll_holds_statements_until_their_frame() {
+        let mut script = Script::parse("press 1; frame 10: release 1; quit").unwrap();
+        assert_eq!(script.poll(0, 0x200), vec![HostEvent::Key(1, true)]);
+        assert_eq!(script.poll(9, 0x200), vec![]);
+        assert_eq!(script.poll(10, 0x200), vec![HostEvent::Key(1, false), HostEvent::Quit]);
+        assert_eq!(script.poll(11, 0x200), vec![]);
+    }
+
+    #[test]
+    fn poll_holds_the_rest_of_the_script_until_the_pc_matches() {
+        let mut script = Script::parse("wait until pc=0x204; press 2").unwrap();
+        assert_eq!(script.poll(0, 0x200), vec![]);
+        assert_eq!(script.poll(50, 0x202), vec![]);
+        assert_eq!(script.poll(50, 0x204), vec![HostEvent::Key(2, true)]);
+    }
+
+    #[test]
+    fn quits_only_with_a_quit_statement() {
+        assert!(Script::parse("press 5\nframe 60: quit").unwrap().quits());
+        assert!(!Script::parse("press 5; frame 60: release 5").unwrap().quits());
+        assert!(!Script::parse("# quit").unwrap().quits());
+    }
+}
@@ -0,0 +1,446 @@
+use std::path::PathBuf;
+
+use super::getopts::Matches;
+
+use super::config::{self, Config};
+use super::filter::Filter;
+use super::font::Font;
+use super::frontend::{FrontendKind, Glyphs};
+use super::keymap::Keymap;
+use super::memory::{Protection, END_RESERVED};
+use super::palette::Palette;
+use super::quirks::{Platform, Quirks};
+use super::rom;
+use super::romdb::RomEntry;
+use super::script::Script;
+use super::sound::{Tone, Waveform};
+
+// Largest multiple of the display windows, screenshots and recordings can be
+// scaled to.
+const MAX_SCALE: u64 = 64;
+
+/// Options for the virtual machine resolved from the command line and the
+/// configuration file. Command line options take precedence.
+#[derive(Clone, Debug)]
+pub struct Settings {
+    // Name of the game from the rom database.
+    pub title: Option<String>,
+
+    // Interpreter the rom was written for and the quirks it relies on.
+    pub platform: Platform,
+    pub quirks: Quirks,
+
+    // Instructions executed every 60Hz frame.
+    pub instructions_per_frame: u32,
+
+    // Where in memory the rom is loaded and execution starts.
+    pub load_address: usize,
+
+    // Hex digits in memory for FX29 and where they are stored.
+    pub font: Font,
+    pub font_offset: usize,
+
+    // How writes to reserved memory and executed code are handled.
+    pub protection: Protection,
+
+    // File the rom's RPL user flags are saved to.
+    pub flags_path: Option<PathBuf>,
+
+    // Colours used to draw the display.
+    pub palette: Palette,
+
+    // Anti-flicker filter applied to the display before it is presented.
+    pub filter: Filter,
+
+    // How many times larger than the display the window starts out.
+    pub scale: u32,
+
+    // Scale the display by whole numbers only so pixels stay square.
+    pub integer_scaling: bool,
+
+    // Start with the window covering the whole screen.
+    pub fullscreen: bool,
+
+    // Show a clickable keypad next to the display.
+    pub keypad: bool,
+
+    // Open the memory viewer window at startup.
+    pub memory_viewer: bool,
+
+    // Where the display is shown and input comes from.
+    pub frontend: FrontendKind,
+
+    // Play the beep through the audio device.
+    pub audio: bool,
+
+    // Pitch, shape and loudness of the beep.
+    pub tone: Tone,
+
+    // Host keys bound to the CHIP-8 keypad.
+    pub keymap: Keymap,
+
+    // Keypad input played back from a script.
+    pub script: Option<Script>,
+
+    // Screenshots to take as (frame, path) pairs.
+    pub screenshots: Vec<(u64, PathBuf)>,
+
+    // How many times larger than the display screenshots are saved.
+    pub screenshot_scale: usize,
+
+    // Record the display to a GIF or YUV4MPEG2 file.
+    pub record: Option<PathBuf>,
+
+    // How many times larger than the display recordings are.
+    pub record_scale: usize,
+
+    // Record the beep to a WAV file.
+    pub record_audio: Option<PathBuf>,
+
+    // Halt after running this many frames.
+    pub frame_limit: Option<u64>,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            title: None,
+            platform: Platform::default(),
+            quirks: Quirks::default(),
+            instructions_per_frame: 8,
+            load_address: END_RESERVED,
+            font: Font::default(),
+            font_offset: 0,
+            protection: Protection::default(),
+            flags_path: None,
+            palette: Palette::default(),
+            filter: Filter::default(),
+            scale: 10,
+            integer_scaling: false,
+            fullscreen: false,
+            keypad: false,
+            memory_viewer: false,
+            frontend: FrontendKind::Sdl,
+            audio: true,
+            tone: Tone::default(),
+            keymap: Keymap::default(),
+            script: None,
+            screenshots: Vec::new(),
+            screenshot_scale: 1,
+            record: None,
+            record_scale: 1,
+            record_audio: None,
+            frame_limit: None,
+        }
+    }
+}
+
+impl Settings {
+    /// Resolves the settings for a rom. Settings for the rom in the rom
+    /// database override the configuration file, and the command line
+    /// overrides both.
+    pub fn load(config: &Config, rom: &RomEntry, matches: &Matches) -> Result<Settings, String> {
+        let mut settings = Settings::default();
+        settings.title = rom.title().map(String::from);
+
+        // Flags are saved per rom, named after its hash like the rom database.
+        if !rom.sha1.is_empty() {
+            settings.flags_path = config::data_dir()
+                .map(|dir| dir.join("flags").join(format!("{}.flags", rom.sha1)));
+        }
+
+        // Quirks follow the platform unless they are given explicitly. Roms
+        // without a known platform keep notch's usual behaviour.
+        if let Some(name) = lookup_rom(config, rom, matches, "cpu", "platform") {
+            settings.platform = try!(Platform::parse(&name));
+            settings.quirks = settings.platform.quirks();
+        }
+        if let Some(list) = lookup_rom(config, rom, matches, "cpu", "quirks") {
+            settings.quirks = try!(Quirks::parse(&list));
+        }
+        if let Some(count) = lookup_rom(config, rom, matches, "cpu", "instructions-per-frame") {
+            settings.instructions_per_frame =
+                try!(parse_positive(&count, "instructions per frame")) as u32;
+        }
+        if let Some(address) = lookup_rom(config, rom, matches, "cpu", "load-address") {
+            settings.load_address = try!(parse_address(&address, "load address"));
+            if settings.load_address % 2 != 0 {
+                return Err(format!("invalid load address '{}', instructions must start on an \
+                                    even address", address.trim()));
+            }
+        }
+        if let Some(spec) = lookup_rom(config, rom, matches, "cpu", "font") {
+            settings.font = try!(Font::parse(&spec));
+        }
+        if let Some(offset) = lookup_rom(config, rom, matches, "cpu", "font-offset") {
+            settings.font_offset = try!(parse_address(&offset, "font offset"));
+        }
+        if let Some(mode) = lookup(config, matches, "cpu", "protect") {
+            settings.protection = try!(Protection::parse(&mode));
+        }
+
+        if let Some(spec) = lookup_rom(config, rom, matches, "display", "palette") {
+            settings.palette = try!(Palette::parse(&spec));
+        }
+        if let Some(spec) = lookup(config, matches, "display", "filter") {
+            settings.filter = try!(Filter::parse(&spec));
+        }
+        if let Some(scale) = lookup(config, matches, "display", "scale") {
+            settings.scale = try!(parse_scale(&scale, "scale")) as u32;
+        }
+        if let Some(scaling) = lookup(config, matches, "display", "scaling") {
+            settings.integer_scaling = match scaling.trim() {
+                "integer" => true,
+                "fit" => false,
+                _ => return Err(format!("invalid scaling '{}', expected integer or fit", scaling)),
+            };
+        }
+        settings.fullscreen = try!(flag(config, matches, "display", "fullscreen"));
+        settings.keypad = try!(flag(config, matches, "display", "keypad"));
+        settings.memory_viewer = try!(flag(config, matches, "display", "memory-viewer"));
+
+        if matches.opt_present("headless") {
+            settings.frontend = FrontendKind::Headless;
+        } else if matches.opt_present("terminal") {
+            let glyphs = match lookup(config, matches, "terminal", "glyphs") {
+                Some(name) => try!(Glyphs::parse(&name)),
+                None => Glyphs::HalfBlock,
+            };
+            settings.frontend = FrontendKind::Terminal(glyphs);
+        }
+        settings.audio = !matches.opt_present("no-audio");
+        if let Some(frequency) = lookup(config, matches, "sound", "frequency") {
+            settings.tone.frequency = match frequency.trim().parse::<f32>() {
+                Ok(frequency) if frequency >= 20.0 && frequency <= 20000.0 => frequency,
+                _ => return Err(format!("invalid frequency '{}', expected 20 to 20000 hertz",
+                                        frequency)),
+            };
+        }
+        if let Some(waveform) = lookup(config, matches, "sound", "waveform") {
+            settings.tone.waveform = try!(Waveform::parse(&waveform));
+        }
+        if let Some(volume) = lookup(config, matches, "sound", "volume") {
+            settings.tone.volume = match volume.trim().parse::<f32>() {
+                Ok(volume) if volume >= 0.0 && volume <= 1.0 => volume,
+                _ => return Err(format!("invalid volume '{}', expected 0 to 1", volume)),
+            };
+        }
+        settings.tone.mute = try!(flag(config, matches, "sound", "mute"));
+
+        if let Some(name) = lookup_rom(config, rom, matches, "input", "keymap") {
+            settings.keymap = match Keymap::preset(name.trim()) {
+                Some(keymap) => keymap,
+                None => return Err(format!("invalid keymap '{}', expected one of: {}",
+                                           name, Keymap::preset_names().join(", "))),
+            };
+        }
+        for (key, host_keys) in config.entries("keymap") {
+            try!(settings.keymap.bind(key, host_keys));
+        }
+
+        // Games have no standard controls, so bindings can also be added for
+        // a single rom in a section named after its file, such as
+        // [keymap:tetris.ch8], or after its SHA-1 like the rom database. They
+        // add to the bindings above rather than replacing them, so binding a
+        // controller button leaves the keyboard working.
+        let rom_name = matches.free.get(0).and_then(|path| rom::file_name(path));
+        let sections = rom_name.into_iter()
+            .chain(Some(rom.sha1.as_str()).into_iter().filter(|sha1| !sha1.is_empty()));
+        for section in sections {
+            for (key, host_keys) in config.entries(&format!("keymap:{}", section)) {
+                try!(settings.keymap.add(key, host_keys));
+            }
+        }
+        if let Some(path) = matches.opt_str("script") {
+            settings.script = Some(try!(Script::load(path)));
+        }
+
+        for spec in matches.opt_strs("screenshot-at-frame") {
+            settings.screenshots.push(try!(parse_capture(&spec)));
+        }
+        if let Some(scale) = lookup(config, matches, "display", "screenshot-scale") {
+            settings.screenshot_scale = try!(parse_scale(&scale, "screenshot scale")) as usize;
+        }
+
+        settings.record = matches.opt_str("record").map(PathBuf::from);
+        if let Some(scale) = lookup(config, matches, "display", "record-scale") {
+            settings.record_scale = try!(parse_scale(&scale, "recording scale")) as usize;
+        }
+        settings.record_audio = matches.opt_str("record-audio").map(PathBuf::from);
+        if let Some(frames) = matches.opt_str("frames") {
+            settings.frame_limit = Some(try!(parse_positive(&frames, "frame count")));
+        }
+        let recording = settings.record.is_some() || settings.record_audio.is_some();
+        if settings.frontend == FrontendKind::Headless && recording && settings.frame_limit.is_none() {
+            return Err("recording in headless mode needs --frames to know when to stop".to_string());
+        }
+
+        // Without a window there is nothing to close, so something else has
+        // to end the run. Scripts end it with quit.
+        let stops = settings.frame_limit.is_some() || !settings.screenshots.is_empty() ||
+                    settings.script.as_ref().map_or(false, |script| script.quits());
+        if settings.frontend == FrontendKind::Headless && !stops {
+            return Err("headless mode needs --frames, --screenshot-at-frame or a --script that \
+                        quits to know when to stop".to_string());
+        }
+
+        Ok(settings)
+    }
+}
+
+/// Parses a whole number greater than zero.
+fn parse_positive(value: &str, name: &str) -> Result<u64, String> {
+    match value.trim().parse::<u64>() {
+        Ok(number) if number > 0 => Ok(number),
+        _ => Err(format!("invalid {} '{}', expected a positive whole number", name, value)),
+    }
+}
+
+/// Parses a scale, which is bounded so the size of the window or image it
+/// multiplies stays reasonable.
+fn parse_scale(value: &str, name: &str) -> Result<u64, String> {
+    match try!(parse_positive(value, name)) {
+        scale if scale <= MAX_SCALE => Ok(scale),
+        _ => Err(format!("invalid {} '{}', expected at most {}", name, value, MAX_SCALE)),
+    }
+}
+
+/// Parses an address written in hex with a 0x prefix or in decimal.
+fn parse_address(value: &str, name: &str) -> Result<usize, String> {
+    let value = value.trim();
+    let result = if value.starts_with("0x") || value.starts_with("0X") {
+        usize::from_str_radix(&value[2..], 16)
+    } else {
+        value.parse::<usize>()
+    };
+
+    match result {
+        Ok(address) => Ok(address),
+        Err(_) => Err(format!("invalid {} '{}', expected an address such as 0x600", name, value)),
+    }
+}
+
+/// Parses a capture request written as "FRAME:PATH".
+fn parse_capture(spec: &str) -> Result<(u64, PathBuf), String> {
+    let mut parts = spec.splitn(2, ':');
+    let frame = parts.next().and_then(|frame| frame.trim().parse::<u64>().ok());
+    let path = parts.next();
+
+    match (frame, path) {
+        (Some(frame), Some(path)) if !path.is_empty() => Ok((frame, PathBuf::from(path))),
+        _ => Err(format!("invalid capture '{}', expected FRAME:FILE", spec)),
+    }
+}
+
+/// Returns true if a command line flag is present or the key of the same name
+/// in a section of the configuration file is set to true.
+fn flag(config: &Config, matches: &Matches, section: &str, key: &str) -> Result<bool, String> {
+    if matches.opt_present(key) {
+        return Ok(true);
+    }
+
+    match config.get(section, key) {
+        Some("true") | Some("yes") | Some("on") => Ok(true),
+        Some("false") | Some("no") | Some("off") | None => Ok(false),
+        Some(value) => Err(format!("invalid value '{}' for {}, expected true or false", value, key)),
+    }
+}
+
+/// Returns the value of a command line option, falling back to the key of the
+/// same name in the rom's database entry and then in a section of the
+/// configuration file.
+fn lookup_rom(config: &Config, rom: &RomEntry, matches: &Matches, section: &str, key: &str)
+              -> Option<String> {
+    matches.opt_str(key)
+        .or_else(|| rom.get(key).map(String::from))
+        .or_else(|| config.get(section, key).map(String::from))
+}
+
+/// Returns the value of a command line option, falling back to the key of the
+/// same name in a section of the configuration file.
+fn lookup(config: &Config, matches: &Matches, section: &str, key: &str) -> Option<String> {
+    matches.opt_str(key).or_else(|| config.get(section, key).map(String::from))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Settings;
+    use super::super::config::Config;
+    use super::super::options;
+    use super::super::quirks::Platform;
+    use super::super::romdb::RomEntry;
+
+    const CONFIG: &'static str = "
+[cpu]
+platform = chip8
+instructions-per-frame = 10
+
+[input]
+keymap = cosmac
+
+[keymap]
+5 = up
+
+[keymap:tetris.ch8]
+4 = pad dpleft
+
+[keymap:0123456789abcdef0123456789abcdef01234567]
+6 = pad dpright
+";
+
+    /// Resolves the settings for a command line and a rom with database
+    /// values.
+    fn load(args: &[&str], values: &[(&str, &str)]) -> Settings {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        let matches = options().parse(&args).unwrap();
+        let config = Config::parse(CONFIG).unwrap();
+        let mut rom = RomEntry::default();
+        rom.sha1 = "0123456789abcdef0123456789abcdef01234567".to_string();
+        for &(key, value) in values {
+            rom.set_default(key, value);
+        }
+        Settings::load(&config, &rom, &matches).unwrap()
+    }
+
+    #[test]
+    fn config_applies_without_a_rom_entry() {
+        let settings = load(&["game.ch8"], &[]);
+        assert_eq!(settings.platform, Platform::Chip8);
+        assert_eq!(settings.instructions_per_frame, 10);
+    }
+
+    #[test]
+    fn rom_entry_overrides_config() {
+        let settings = load(&["game.ch8"], &[("platform", "schip"),
+                                             ("instructions-per-frame", "30")]);
+        assert_eq!(settings.platform, Platform::SuperChip);
+        assert_eq!(settings.instructions_per_frame, 30);
+    }
+
+    #[test]
+    fn command_line_overrides_rom_entry() {
+        let settings = load(&["--platform", "xochip", "--instructions-per-frame", "100",
+                              "game.ch8"],
+                            &[("platform", "schip"), ("instructions-per-frame", "30")]);
+        assert_eq!(settings.platform, Platform::XoChip);
+        assert_eq!(settings.instructions_per_frame, 100);
+    }
+
+    #[test]
+    fn keymap_sections_add_to_the_layout() {
+        let settings = load(&["roms/tetris.ch8"], &[]);
+        assert_eq!(settings.keymap.get("q"), Some(4));
+        assert_eq!(settings.keymap.get("up"), Some(5));
+        assert_eq!(settings.keymap.get("w"), None);
+
+        // Bindings for the file name and for the hash both apply.
+        assert_eq!(settings.keymap.get("pad dpleft"), Some(4));
+        assert_eq!(settings.keymap.get("pad dpright"), Some(6));
+    }
+
+    #[test]
+    fn keymap_sections_for_other_roms_are_ignored() {
+        let settings = load(&["pong.ch8"], &[]);
+        assert_eq!(settings.keymap.get("pad dpleft"), None);
+    }
+}
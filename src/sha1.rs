@@ -0,0 +1,89 @@
+use super::byteorder::{BigEndian, ByteOrder};
+
+// Data is hashed in blocks of this many bytes.
+const BLOCK_SIZE: usize = 64;
+
+/// Computes the SHA-1 digest of some data as a lowercase hex string, which is
+/// how roms are identified in the rom database.
+pub fn sha1_hex(data: &[u8]) -> String {
+    sha1(data).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Computes the SHA-1 digest of some data.
+pub fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut state: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+
+    // Pad the message with a set bit, zeros up to 8 bytes short of a whole
+    // block, then the length of the message in bits.
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % BLOCK_SIZE != BLOCK_SIZE - 8 {
+        message.push(0);
+    }
+    let mut length = [0; 8];
+    BigEndian::write_u64(&mut length, data.len() as u64 * 8);
+    message.extend_from_slice(&length);
+
+    for block in message.chunks(BLOCK_SIZE) {
+        let mut words = [0u32; 80];
+        for i in 0..16 {
+            words[i] = BigEndian::read_u32(&block[i * 4..]);
+        }
+        for i in 16..80 {
+            words[i] = (words[i - 3] ^ words[i - 8] ^ words[i - 14] ^ words[i - 16]).rotate_left(1);
+        }
+
+        let (mut a, mut b, mut c, mut d, mut e) = (state[0], state[1], state[2], state[3], state[4]);
+        for i in 0..80 {
+            let (f, k) = match i {
+                0...19 => ((b & c) | (!b & d), 0x5A827999),
+                20...39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40...59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+
+            let temp = a.rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(words[i]);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+
+        state[0] = state[0].wrapping_add(a);
+        state[1] = state[1].wrapping_add(b);
+        state[2] = state[2].wrapping_add(c);
+        state[3] = state[3].wrapping_add(d);
+        state[4] = state[4].wrapping_add(e);
+    }
+
+    let mut digest = [0; 20];
+    for i in 0..5 {
+        BigEndian::write_u32(&mut digest[i * 4..], state[i]);
+    }
+    digest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::sha1_hex;
+
+    #[test]
+    fn matches_the_standard_test_vectors() {
+        assert_eq!(sha1_hex(b""), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+        assert_eq!(sha1_hex(b"abc"), "a9993e364706816aba3e25717850c26c9cd0d89d");
+    }
+
+    #[test]
+    fn hashes_messages_longer_than_one_block() {
+        let message = b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq";
+        assert_eq!(sha1_hex(message), "84983e441c3bd26ebaae4aa1f95129e5e54670f1");
+
+        let million = vec![b'a'; 1000000];
+        assert_eq!(sha1_hex(&million), "34aa973cd4c4daa4f61eeb2bdbad27316534016f");
+    }
+}
@@ -1,64 +1,456 @@
+use std::f32::consts::PI;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use super::rand::{Rng, XorShiftRng};
 use super::sdl2::audio::{AudioCallback, AudioSpecDesired};
 use super::sdl2;
 
+// Rate the beep is generated at, in samples per second.
+pub const SAMPLE_RATE: i32 = 44100;
+
+// Time taken for the beep to fade in or out, in seconds. Cutting the wave off
+// abruptly makes an audible click.
+const ENVELOPE_TIME: f32 = 0.005;
+
+// Emulated frames per second, which the sound timer counts down at.
+const FRAME_RATE: i64 = 60;
+
+// How many frames the emulator may run ahead of or fall behind the audio
+// device before the beep is resynchronised, for example when the host falls
+// behind or the device stalls.
+const MAX_DRIFT: i64 = 6;
+
+// Number of changes to the beep that can wait for the audio callback. The beep
+// changes at most once a frame and the callback runs several times a frame, so
+// the queue only fills up if the device stops.
+const QUEUE_SIZE: usize = 64;
+
+/// Shape of the beep's wave.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Waveform {
+    Square,
+    Sine,
+    Triangle,
+    Sawtooth,
+    Noise,
+}
+
+impl Waveform {
+    pub fn parse(name: &str) -> Result<Waveform, String> {
+        match name.trim() {
+            "square" => Ok(Waveform::Square),
+            "sine" => Ok(Waveform::Sine),
+            "triangle" => Ok(Waveform::Triangle),
+            "sawtooth" => Ok(Waveform::Sawtooth),
+            "noise" => Ok(Waveform::Noise),
+            _ => Err(format!("invalid waveform '{}', expected square, sine, triangle, \
+                              sawtooth or noise", name)),
+        }
+    }
+}
+
+/// How the beep sounds.
+#[derive(Clone, Copy, Debug)]
+pub struct Tone {
+    // Pitch of the beep in hertz.
+    pub frequency: f32,
+
+    pub waveform: Waveform,
+
+    // Loudness from 0 (silent) to 1.
+    pub volume: f32,
+
+    // Start with the beep silenced, both through the speakers and in
+    // recordings.
+    pub mute: bool,
+}
+
+impl Default for Tone {
+    fn default() -> Tone {
+        Tone {
+            frequency: 440.0,
+            waveform: Waveform::Square,
+            volume: 0.25,
+            mute: false,
+        }
+    }
+}
+
+/// A change of the beep at the start of an emulated frame.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct SoundEvent {
+    frame: u64,
+    beeping: bool,
+
+    // Set when the virtual machine was reset and counts frames from 0 again.
+    restart: bool,
+}
+
+impl SoundEvent {
+    /// Packs the event into a single word so it can be stored atomically.
+    fn encode(&self) -> usize {
+        (self.frame as usize) << 2 | (self.restart as usize) << 1 | self.beeping as usize
+    }
+
+    fn decode(word: usize) -> SoundEvent {
+        SoundEvent {
+            frame: (word >> 2) as u64,
+            beeping: word & 1 != 0,
+            restart: word & 2 != 0,
+        }
+    }
+}
+
+/// State shared between the emulator and the audio callback. Changes to the
+/// beep go through a fixed size ring buffer with a single writer and a single
+/// reader, so neither side ever locks or waits on the other.
+struct SharedState {
+    slots: Vec<AtomicUsize>,
+
+    // Number of events written and read so far. Each side only stores its own
+    // counter, and an event is published by storing the write counter after
+    // the slot.
+    written: AtomicUsize,
+    read: AtomicUsize,
+
+    // Whether the beep is silenced.
+    muted: AtomicBool,
+}
+
+impl SharedState {
+    fn new(muted: bool) -> SharedState {
+        SharedState {
+            slots: (0..QUEUE_SIZE).map(|_| AtomicUsize::new(0)).collect(),
+            written: AtomicUsize::new(0),
+            read: AtomicUsize::new(0),
+            muted: AtomicBool::new(muted),
+        }
+    }
+
+    /// Adds an event to the queue. Returns false if the queue is full.
+    fn push(&self, event: SoundEvent) -> bool {
+        let written = self.written.load(Ordering::Relaxed);
+        let read = self.read.load(Ordering::Acquire);
+        if written.wrapping_sub(read) >= QUEUE_SIZE {
+            return false;
+        }
+
+        self.slots[written % QUEUE_SIZE].store(event.encode(), Ordering::Relaxed);
+        self.written.store(written.wrapping_add(1), Ordering::Release);
+        true
+    }
+
+    /// Takes the oldest event from the queue.
+    fn pop(&self) -> Option<SoundEvent> {
+        let read = self.read.load(Ordering::Relaxed);
+        let written = self.written.load(Ordering::Acquire);
+        if read == written {
+            return None;
+        }
+
+        let event = SoundEvent::decode(self.slots[read % QUEUE_SIZE].load(Ordering::Relaxed));
+        self.read.store(read.wrapping_add(1), Ordering::Release);
+        Some(event)
+    }
+}
+
 pub struct Sound {
-    // SDL objects for communication with the window system.
-    audio_device: sdl2::audio::AudioDevice<BeepCallback>,
+    // SDL objects for communication with the window system. Without a device
+    // the beep is silent.
+    audio_device: Option<sdl2::audio::AudioDevice<BeepCallback>>,
+
+    // Queue of changes to the beep shared with the audio callback.
+    shared: Option<Arc<SharedState>>,
 
     // When true beeping audio will play.
-    pub beeping: bool,
+    beeping: bool,
 }
 
 impl Sound {
-    pub fn new(sdl_context: &sdl2::Sdl) -> Sound {
-        let audio_subsystem = sdl_context.audio().unwrap();
+    pub fn new(sdl_context: &sdl2::Sdl, tone: Tone) -> sdl2::SdlResult<Sound> {
+        let audio_subsystem = try!(sdl_context.audio());
 
         // Setup beep sound parameters.
         let desired_spec = AudioSpecDesired {
-            freq: Some(44100), // I think this is healthy?
+            freq: Some(SAMPLE_RATE),
             channels: Some(1), // Mono.
             samples: None,     // Default sample size.
         };
-        let device = audio_subsystem.open_playback(None, &desired_spec, |spec| {
+        let shared = Arc::new(SharedState::new(tone.mute));
+        let device = try!(audio_subsystem.open_playback(None, &desired_spec, |spec| {
             BeepCallback {
-                phase_inc: 440.0 / spec.freq as f32,
-                phase: 0.0,
-                volume: 0.25
+                synth: Synth::new(tone, spec.freq),
+                shared: shared.clone(),
+                pending: None,
+                sample_rate: spec.freq as i64,
+                clock: 0,
+                origin: None,
             }
-        }).unwrap();
+        }));
+
+        // The device keeps running so the beep can fade out after it is
+        // stopped instead of being cut off.
+        device.resume();
+
+        Ok(Sound {
+            audio_device: Some(device),
+            shared: Some(shared),
+            beeping: false,
+        })
+    }
+
+    /// Silences the beep or brings it back. The device keeps running either
+    /// way so the beep can be unmuted at any time.
+    pub fn set_muted(&mut self, muted: bool) {
+        if let Some(ref shared) = self.shared {
+            shared.muted.store(muted, Ordering::Relaxed);
+        }
+    }
 
+    /// Creates a sound backend that plays nothing, for machines without an
+    /// audio device.
+    pub fn silent() -> Sound {
         Sound {
-            audio_device: device,
+            audio_device: None,
+            shared: None,
             beeping: false,
         }
     }
 
-    pub fn handle_sound(&self) {
+    /// Starts or stops the beep from the start of an emulated frame. The
+    /// audio callback plays the change once the device reaches that frame, so
+    /// a beep lasts exactly as many frames as the sound timer ran for.
+    pub fn set_beeping(&mut self, frame: u64, beeping: bool) {
+        if beeping == self.beeping {
+            return;
+        }
+        self.beeping = beeping;
+        self.send(SoundEvent { frame: frame, beeping: beeping, restart: false });
+    }
+
+    /// Stops the beep and starts counting frames from 0 again after the
+    /// virtual machine is reset. The audio callback lines frame 0 up with
+    /// the device straight away rather than waiting to drift out of sync.
+    pub fn restart(&mut self) {
+        self.beeping = false;
+        self.send(SoundEvent { frame: 0, beeping: false, restart: true });
+    }
+
+    /// Queues a change for the audio callback. Changes are dropped if the
+    /// device has stopped taking them.
+    fn send(&self, event: SoundEvent) {
+        if let Some(ref shared) = self.shared {
+            shared.push(event);
+        }
+    }
+}
+
+impl Drop for Sound {
+    /// Stops playback before the queue of changes is dropped.
+    fn drop(&mut self) {
+        if let Some(ref audio_device) = self.audio_device {
+            audio_device.pause();
+        }
+    }
+}
+
+/// Generates the samples of the beep.
+pub struct Synth {
+    tone: Tone,
+
+    // Position within the current cycle of the wave from 0 to 1, and how far
+    // it moves with every sample.
+    phase: f32,
+    phase_inc: f32,
+
+    // Current loudness of the envelope from 0 to 1, and how far it moves with
+    // every sample.
+    level: f32,
+    level_inc: f32,
+
+    // Source and current value of the noise waveform.
+    rng: XorShiftRng,
+    noise: f32,
+
+    // When true the beep fades in, otherwise it fades out.
+    pub beeping: bool,
+
+    // When true every sample is silent. The wave keeps running underneath so
+    // unmuting picks it up where it would have been.
+    pub muted: bool,
+}
+
+impl Synth {
+    pub fn new(tone: Tone, sample_rate: i32) -> Synth {
+        Synth {
+            tone: tone,
+            phase: 0.0,
+            phase_inc: tone.frequency / sample_rate as f32,
+            level: 0.0,
+            level_inc: 1.0 / (ENVELOPE_TIME * sample_rate as f32),
+            rng: XorShiftRng::new_unseeded(),
+            noise: 1.0,
+            beeping: false,
+            muted: tone.mute,
+        }
+    }
+
+    /// Returns the next sample of the beep.
+    pub fn sample(&mut self) -> f32 {
         if self.beeping {
-            self.audio_device.resume();
+            self.level = (self.level + self.level_inc).min(1.0);
         } else {
-            self.audio_device.pause();
+            self.level = (self.level - self.level_inc).max(0.0);
+        }
+
+        let phase = self.phase;
+        let value = match self.tone.waveform {
+            Waveform::Square => if phase < 0.5 { 1.0 } else { -1.0 },
+            Waveform::Sine => (phase * 2.0 * PI).sin(),
+            Waveform::Triangle => 1.0 - 4.0 * (phase - 0.5).abs(),
+            Waveform::Sawtooth => 2.0 * phase - 1.0,
+            Waveform::Noise => self.noise,
+        };
+
+        // Noise picks a new random level every half cycle so its pitch still
+        // follows the frequency.
+        let next = phase + self.phase_inc;
+        if self.tone.waveform == Waveform::Noise && (next >= 1.0 || (phase < 0.5 && next >= 0.5)) {
+            self.noise = if self.rng.gen::<bool>() { 1.0 } else { -1.0 };
         }
+        self.phase = next % 1.0;
+
+        let volume = if self.muted { 0.0 } else { self.tone.volume };
+        value * volume * self.level
     }
 }
 
 struct BeepCallback {
-    phase_inc: f32,
-    phase: f32,
-    volume: f32
+    synth: Synth,
+
+    // Changes to the beep sent by the emulator, and the next one to play.
+    shared: Arc<SharedState>,
+    pending: Option<SoundEvent>,
+
+    sample_rate: i64,
+
+    // Number of samples generated so far.
+    clock: i64,
+
+    // The sample at which emulated frame 0 starts playing.
+    origin: Option<i64>,
+}
+
+impl BeepCallback {
+    /// Returns the sample at which a frame starts playing.
+    fn frame_start(&self, origin: i64, frame: u64) -> i64 {
+        origin + frame as i64 * self.sample_rate / FRAME_RATE
+    }
+
+    /// Takes the next change from the queue, realigning frames with the
+    /// device when it is too far ahead or behind. Frames are scheduled one
+    /// buffer ahead since the emulator is already in the middle of the frame
+    /// the change was sent in.
+    fn next_event(&mut self, buffer_len: i64) -> Option<SoundEvent> {
+        if self.pending.is_none() {
+            self.pending = self.shared.pop();
+
+            if let Some(event) = self.pending {
+                let drift = self.sample_rate * MAX_DRIFT / FRAME_RATE;
+                let in_sync = !event.restart && match self.origin {
+                    Some(origin) => {
+                        let start = self.frame_start(origin, event.frame);
+                        start >= self.clock - drift && start <= self.clock + buffer_len + drift
+                    },
+                    None => false,
+                };
+
+                if !in_sync {
+                    self.origin = Some(self.clock + buffer_len - self.frame_start(0, event.frame));
+                }
+            }
+        }
+
+        self.pending
+    }
 }
 
 impl AudioCallback for BeepCallback {
     type Channel = f32;
 
     fn callback(&mut self, out: &mut [f32]) {
-        // Generate a square wave.
+        let buffer_len = out.len() as i64;
+        self.synth.muted = self.shared.muted.load(Ordering::Relaxed);
+
         for x in out.iter_mut() {
-            *x = match self.phase {
-                0.0...0.5 => self.volume,
-                _ => -self.volume
-            };
-            self.phase = (self.phase + self.phase_inc) % 1.0;
+            // Apply every change due by this sample.
+            while let Some(event) = self.next_event(buffer_len) {
+                let origin = self.origin.unwrap_or(0);
+                if self.frame_start(origin, event.frame) > self.clock {
+                    break;
+                }
+                self.synth.beeping = event.beeping;
+                self.pending = None;
+            }
+
+            *x = self.synth.sample();
+            self.clock += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SharedState, SoundEvent, Synth, Tone, QUEUE_SIZE};
+
+    fn event(frame: u64, beeping: bool) -> SoundEvent {
+        SoundEvent { frame: frame, beeping: beeping, restart: false }
+    }
+
+    #[test]
+    fn queue_returns_events_in_order() {
+        let queue = SharedState::new(false);
+        assert_eq!(queue.pop(), None);
+
+        // Go round the ring a few times.
+        for frame in 0..QUEUE_SIZE as u64 * 3 {
+            assert!(queue.push(event(frame, frame % 2 == 0)));
+            assert!(queue.push(event(frame + 1, false)));
+            assert_eq!(queue.pop(), Some(event(frame, frame % 2 == 0)));
+            assert_eq!(queue.pop(), Some(event(frame + 1, false)));
         }
+        assert_eq!(queue.pop(), None);
+    }
+
+    #[test]
+    fn queue_refuses_events_when_full() {
+        let queue = SharedState::new(false);
+        for frame in 0..QUEUE_SIZE as u64 {
+            assert!(queue.push(event(frame, true)));
+        }
+        assert!(!queue.push(event(100, true)));
+
+        assert_eq!(queue.pop(), Some(event(0, true)));
+        assert!(queue.push(event(100, true)));
+    }
+
+    #[test]
+    fn events_survive_encoding() {
+        let restart = SoundEvent { frame: 0, beeping: false, restart: true };
+        assert_eq!(SoundEvent::decode(restart.encode()), restart);
+        assert_eq!(SoundEvent::decode(event(123456, true).encode()), event(123456, true));
+    }
+
+    #[test]
+    fn muted_synth_is_silent() {
+        let mut tone = Tone::default();
+        tone.mute = true;
+        let mut synth = Synth::new(tone, 8000);
+        synth.beeping = true;
+        assert!((0..400).all(|_| synth.sample() == 0.0));
+
+        synth.muted = false;
+        assert!((0..400).any(|_| synth.sample() != 0.0));
     }
 }
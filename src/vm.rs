@@ -1,24 +1,100 @@
+use std::thread::sleep;
+use std::time::Duration;
+
+use super::time;
+
 use super::cpu::Cpu;
 use super::interconnect::Interconnect;
+use super::script::Script;
+use super::settings::Settings;
+use super::watcher::RomWatcher;
+
+// Duration of a 60Hz frame in nanoseconds.
+const FRAME_DURATION: u64 = 1000000000 / 60;
 
 pub struct VirtualMachine {
     cpu: Cpu,
+
+    // Frames are paced to 60Hz when a human is watching.
+    realtime: bool,
+
+    // Reloads the rom when it changes on disk if watching is enabled.
+    watcher: Option<RomWatcher>,
+
+    // Plays back keypad input from a script.
+    script: Option<Script>,
 }
 
 impl VirtualMachine {
-    pub fn new(rom: Vec<u8>) -> VirtualMachine {
+    pub fn new(rom: Vec<u8>, settings: &Settings) -> Result<VirtualMachine, String> {
         // Create a clean cpu state and interconnect (manages memory/input/etc).
-        let interconnect = Interconnect::new(rom);
-        let cpu = Cpu::new(interconnect);
+        let interconnect = try!(Interconnect::new(rom, settings));
+        let realtime = interconnect.is_realtime();
+        let cpu = Cpu::new(interconnect, settings);
 
-        VirtualMachine {
+        Ok(VirtualMachine {
             cpu: cpu,
-        }
+            realtime: realtime,
+            watcher: None,
+            script: settings.script.clone(),
+        })
+    }
+
+    /// Watch a rom file and reset the virtual machine with its new contents
+    /// whenever it is modified.
+    pub fn watch(&mut self, path: &str) {
+        self.watcher = Some(RomWatcher::new(path));
     }
 
-    /// Wrapper for the cpu's run function. Simply starts code execution at the
+    /// Runs the cpu until a close is requested. Code execution starts at the
     /// end of reserved program memory.
     pub fn run(&mut self) {
-        self.cpu.run();
+        let mut frame_end = time::precise_time_ns() + FRAME_DURATION;
+
+        while !self.cpu.halted() {
+            // Nothing runs while paused, but the frontend is still presented
+            // and polled every frame so the user can inspect memory and
+            // resume.
+            if self.cpu.paused() {
+                self.cpu.idle_frame();
+                sleep(Duration::new(0, FRAME_DURATION as u32));
+                frame_end = time::precise_time_ns() + FRAME_DURATION;
+                continue;
+            }
+
+            let frame_ended = self.cpu.step();
+
+            if let Some(ref mut script) = self.script {
+                for event in script.poll(self.cpu.frame(), self.cpu.pc()) {
+                    self.cpu.handle_event(event);
+                }
+            }
+
+            if !frame_ended {
+                continue;
+            }
+
+            // Sleep off the rest of the frame. If the host falls behind by
+            // more than a frame the schedule is reset rather than rushing to
+            // catch up.
+            if self.realtime {
+                let current_time = time::precise_time_ns();
+                if current_time < frame_end {
+                    sleep(Duration::new(0, (frame_end - current_time) as u32));
+                    frame_end += FRAME_DURATION;
+                } else {
+                    frame_end = current_time + FRAME_DURATION;
+                }
+            }
+
+            if let Some(ref mut watcher) = self.watcher {
+                if let Some(rom) = watcher.poll() {
+                    println!("Reloading rom: {}", watcher.path());
+                    if let Err(why) = self.cpu.reset(rom) {
+                        println!("notch: cannot reload '{}': {}", watcher.path(), why);
+                    }
+                }
+            }
+        }
     }
 }
@@ -0,0 +1,75 @@
+use std::fs;
+use std::path::Path;
+use std::time::SystemTime;
+
+use super::rom;
+use super::time;
+
+// How often the rom file is checked for changes in milliseconds.
+const WATCH_DELAY: u64 = 250;
+
+pub struct RomWatcher {
+    // Path of the rom as given on the command line, which may name a file
+    // inside a zip archive.
+    path: String,
+
+    // Modification time of the rom when it was last loaded.
+    modified: Option<SystemTime>,
+
+    // Time in milliseconds when the file should be checked again.
+    next_poll: u64,
+}
+
+impl RomWatcher {
+    pub fn new(path: &str) -> RomWatcher {
+        let modified = RomWatcher::modified(path);
+
+        RomWatcher {
+            path: path.to_string(),
+            modified: modified,
+            next_poll: 0,
+        }
+    }
+
+    /// Checks if the rom has been modified since it was last loaded and
+    /// returns the new contents if so. Files that cannot be read or are empty
+    /// are assumed to be mid-write and are tried again on the next poll.
+    /// Settings that come with the rom are only applied on the first load.
+    pub fn poll(&mut self) -> Option<Vec<u8>> {
+        let current_time = time::precise_time_ns() / 1000000;
+        if current_time < self.next_poll {
+            return None;
+        }
+        self.next_poll = current_time + WATCH_DELAY;
+
+        let modified = RomWatcher::modified(&self.path);
+        if modified.is_none() || modified == self.modified {
+            return None;
+        }
+
+        match rom::read_bin(&self.path) {
+            Ok(rom) => {
+                if rom.program.is_empty() {
+                    return None;
+                }
+                self.modified = modified;
+                Some(rom.program)
+            },
+            Err(why) => {
+                println!("notch: cannot reload '{}': {}", self.path, why);
+                None
+            },
+        }
+    }
+
+    /// Returns the path of the rom being watched.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Modification time of the file holding the rom.
+    fn modified(path: &str) -> Option<SystemTime> {
+        let (file, _) = rom::split_path(path);
+        fs::metadata(Path::new(file)).and_then(|metadata| metadata.modified()).ok()
+    }
+}
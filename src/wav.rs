@@ -0,0 +1,64 @@
+use std::io::{self, Seek, SeekFrom, Write};
+
+use super::byteorder::{LittleEndian, WriteBytesExt};
+
+// Size of the header in front of the samples.
+const HEADER_SIZE: u32 = 44;
+
+/// Writes mono 16-bit PCM samples to a WAV file. The sizes in the header are
+/// only known once every sample is written, so they are filled in by `finish`.
+pub struct WavWriter<W: Write + Seek> {
+    out: W,
+
+    // Number of samples written so far.
+    samples: u32,
+}
+
+impl<W: Write + Seek> WavWriter<W> {
+    /// Writes the header of a recording at the given sample rate.
+    pub fn new(mut out: W, sample_rate: u32) -> io::Result<WavWriter<W>> {
+        try!(out.write_all(b"RIFF"));
+        try!(out.write_u32::<LittleEndian>(HEADER_SIZE - 8));
+        try!(out.write_all(b"WAVE"));
+
+        // Format chunk for uncompressed mono 16-bit samples.
+        try!(out.write_all(b"fmt "));
+        try!(out.write_u32::<LittleEndian>(16));
+        try!(out.write_u16::<LittleEndian>(1));
+        try!(out.write_u16::<LittleEndian>(1));
+        try!(out.write_u32::<LittleEndian>(sample_rate));
+        try!(out.write_u32::<LittleEndian>(sample_rate * 2));
+        try!(out.write_u16::<LittleEndian>(2));
+        try!(out.write_u16::<LittleEndian>(16));
+
+        try!(out.write_all(b"data"));
+        try!(out.write_u32::<LittleEndian>(0));
+
+        Ok(WavWriter {
+            out: out,
+            samples: 0,
+        })
+    }
+
+    /// Adds samples ranging from -1 to 1.
+    pub fn write_samples(&mut self, samples: &[f32]) -> io::Result<()> {
+        for &sample in samples {
+            let value = (sample.max(-1.0).min(1.0) * i16::max_value() as f32) as i16;
+            try!(self.out.write_i16::<LittleEndian>(value));
+        }
+        self.samples += samples.len() as u32;
+        Ok(())
+    }
+
+    /// Fills in the sizes in the header.
+    pub fn finish(&mut self) -> io::Result<()> {
+        let data_size = self.samples * 2;
+
+        try!(self.out.seek(SeekFrom::Start(4)));
+        try!(self.out.write_u32::<LittleEndian>(HEADER_SIZE - 8 + data_size));
+        try!(self.out.seek(SeekFrom::Start(HEADER_SIZE as u64 - 4)));
+        try!(self.out.write_u32::<LittleEndian>(data_size));
+        try!(self.out.seek(SeekFrom::End(0)));
+        self.out.flush()
+    }
+}
@@ -0,0 +1,186 @@
+use super::inflate::inflate;
+use super::png::crc32;
+
+// Signatures of the records a zip archive is made of.
+const LOCAL_HEADER: u32 = 0x04034B50;
+const CENTRAL_HEADER: u32 = 0x02014B50;
+const END_OF_DIRECTORY: u32 = 0x06054B50;
+
+// Size of the end of central directory record without its comment, which may
+// be up to 65535 bytes long.
+const END_OF_DIRECTORY_SIZE: usize = 22;
+
+// Compression methods.
+const STORED: u16 = 0;
+const DEFLATED: u16 = 8;
+
+/// Returns true if the data starts like a zip archive.
+pub fn is_zip(data: &[u8]) -> bool {
+    data.len() >= 4 && read_u32(data) == LOCAL_HEADER
+}
+
+/// A file stored in an archive, as listed in the central directory.
+struct ZipEntry {
+    name: String,
+    method: u16,
+    crc: u32,
+    compressed_size: usize,
+    size: usize,
+
+    // Offset of the file's local header.
+    offset: usize,
+}
+
+/// A zip archive read into memory, such as a pack of roms.
+pub struct ZipArchive {
+    data: Vec<u8>,
+    entries: Vec<ZipEntry>,
+}
+
+impl ZipArchive {
+    pub fn parse(data: Vec<u8>) -> Result<ZipArchive, String> {
+        // The central directory is found through the record at the end of
+        // the archive, searching backwards past any comment.
+        let mut end = match data.len().checked_sub(END_OF_DIRECTORY_SIZE) {
+            Some(end) => end,
+            None => return Err("not a zip archive".to_string()),
+        };
+        while read_u32(&data[end..]) != END_OF_DIRECTORY {
+            if end == 0 {
+                return Err("cannot find the zip central directory".to_string());
+            }
+            end -= 1;
+        }
+
+        let count = read_u16(&data[end + 10..]) as usize;
+        let mut pos = read_u32(&data[end + 16..]) as usize;
+
+        let mut entries = Vec::new();
+        for _ in 0..count {
+            if pos + 46 > data.len() || read_u32(&data[pos..]) != CENTRAL_HEADER {
+                return Err("corrupt zip central directory".to_string());
+            }
+            let header = &data[pos..];
+            let name_len = read_u16(&header[28..]) as usize;
+            let extra_len = read_u16(&header[30..]) as usize;
+            let comment_len = read_u16(&header[32..]) as usize;
+            if pos + 46 + name_len > data.len() {
+                return Err("corrupt zip central directory".to_string());
+            }
+
+            entries.push(ZipEntry {
+                name: String::from_utf8_lossy(&header[46..46 + name_len]).into_owned(),
+                method: read_u16(&header[10..]),
+                crc: read_u32(&header[16..]),
+                compressed_size: read_u32(&header[20..]) as usize,
+                size: read_u32(&header[24..]) as usize,
+                offset: read_u32(&header[42..]) as usize,
+            });
+            pos += 46 + name_len + extra_len + comment_len;
+        }
+
+        Ok(ZipArchive {
+            data: data,
+            entries: entries,
+        })
+    }
+
+    /// Names of the files in the archive, leaving out directories.
+    pub fn names(&self) -> Vec<&str> {
+        self.entries.iter()
+            .map(|entry| entry.name.as_str())
+            .filter(|name| !name.ends_with('/'))
+            .collect()
+    }
+
+    /// Extracts a file from the archive.
+    pub fn read(&self, name: &str) -> Result<Vec<u8>, String> {
+        let entry = match self.entries.iter().find(|entry| entry.name == name) {
+            Some(entry) => entry,
+            None => return Err(format!("no file named '{}' in the archive", name)),
+        };
+
+        // The data follows the local header, whose name and extra field may
+        // differ in length from the central directory's.
+        let pos = entry.offset;
+        if pos + 30 > self.data.len() || read_u32(&self.data[pos..]) != LOCAL_HEADER {
+            return Err(format!("corrupt zip entry '{}'", name));
+        }
+        let name_len = read_u16(&self.data[pos + 26..]) as usize;
+        let extra_len = read_u16(&self.data[pos + 28..]) as usize;
+        let start = pos + 30 + name_len + extra_len;
+        if start + entry.compressed_size > self.data.len() {
+            return Err(format!("corrupt zip entry '{}'", name));
+        }
+        let compressed = &self.data[start..start + entry.compressed_size];
+
+        let contents = match entry.method {
+            STORED => compressed.to_vec(),
+            DEFLATED => try!(inflate(compressed).map_err(|why| format!("'{}': {}", name, why))),
+            method => return Err(format!("'{}' uses unsupported compression method {}",
+                                         name, method)),
+        };
+
+        if contents.len() != entry.size || crc32(0, &contents) != entry.crc {
+            return Err(format!("'{}' is corrupt, its checksum does not match", name));
+        }
+        Ok(contents)
+    }
+}
+
+// Fields are read a byte at a time since they are rarely aligned.
+fn read_u16(data: &[u8]) -> u16 {
+    data[0] as u16 | (data[1] as u16) << 8
+}
+
+fn read_u32(data: &[u8]) -> u32 {
+    read_u16(data) as u32 | (read_u16(&data[2..]) as u32) << 16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ZipArchive;
+
+    // An archive made by Python's zipfile holding a directory, a stored rom,
+    // a deflated rom and a text file.
+    const ROMS: &'static [u8] = include_bytes!("../tests/fixtures/roms.zip");
+
+    // The contents of games/maze.ch8.
+    const MAZE: [u8; 4] = [0x00, 0xE0, 0x12, 0x02];
+
+    #[test]
+    fn lists_files_without_directories() {
+        let archive = ZipArchive::parse(ROMS.to_vec()).unwrap();
+        assert_eq!(archive.names(), vec!["games/maze.ch8", "games/text.ch8", "readme.txt"]);
+    }
+
+    #[test]
+    fn reads_stored_and_deflated_files() {
+        let archive = ZipArchive::parse(ROMS.to_vec()).unwrap();
+        assert_eq!(archive.read("games/maze.ch8").unwrap(), MAZE.to_vec());
+        assert_eq!(archive.read("readme.txt").unwrap(), b"hello".to_vec());
+
+        let text = archive.read("games/text.ch8").unwrap();
+        assert!(text.starts_with(b"0 fish, 0 bird, 0 cat; "));
+        assert_eq!(text.len(), 283);
+
+        assert!(archive.read("games/missing.ch8").is_err());
+    }
+
+    #[test]
+    fn refuses_files_whose_checksum_does_not_match() {
+        let mut data = ROMS.to_vec();
+        let pos = data.windows(MAZE.len()).position(|window| window == MAZE).unwrap();
+        data[pos + 1] ^= 0xFF;
+
+        let archive = ZipArchive::parse(data).unwrap();
+        assert!(archive.read("games/maze.ch8").unwrap_err().contains("checksum"));
+        assert!(archive.read("readme.txt").is_ok());
+    }
+
+    #[test]
+    fn refuses_data_that_is_not_an_archive() {
+        assert!(ZipArchive::parse(b"not a zip".to_vec()).is_err());
+        assert!(ZipArchive::parse(ROMS[..ROMS.len() - 30].to_vec()).is_err());
+    }
+}